@@ -0,0 +1,422 @@
+use crate::{
+    bitboard::{square_position, squares, Position},
+    board::{GameMode, GameSettings, MoveEvent, MoveHistory, PlayerTurn},
+    book::OpeningBook,
+    difficulty::{Personality, PlayStyle},
+    pieces::{Move, PieceColor, PieceType},
+    tablebase::Tablebases,
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 3,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 5,
+        PieceType::Queen => 9,
+        PieceType::King => 0,
+    }
+}
+
+/// Material balance from the point of view of the given color.
+fn evaluate(color: PieceColor, position: &Position) -> i32 {
+    [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ]
+    .iter()
+    .map(|&piece_type| {
+        let count = |color| position.of(color, piece_type).count_ones() as i32;
+        (count(color) - count(color.opposite())) * piece_value(piece_type)
+    })
+    .sum()
+}
+
+/// A pawn in the centipawns searches score in.
+const PAWN: i32 = 100;
+
+/// Centipawns a piece is worth for each square closer it stands to the king its style aims for.
+const STYLE_BONUS: i32 = 4;
+
+/// How much worse than the best move, in centipawns, a deliberately worse move may be.
+const BLUNDER_MARGIN: i32 = 300;
+
+/// Centipawns for how well `color`'s pieces suit `style`: how close they stand to the enemy king
+/// when aggressive, or to their own when defensive.
+fn style_bonus(style: PlayStyle, color: PieceColor, position: &Position) -> i32 {
+    let king_color = match style {
+        PlayStyle::Balanced => return 0,
+        PlayStyle::Aggressive => color.opposite(),
+        PlayStyle::Defensive => color,
+    };
+    let king = match squares(position.of(king_color, PieceType::King)).next() {
+        Some(king) => square_position(king),
+        None => return 0,
+    };
+
+    [
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ]
+    .iter()
+    .flat_map(|&piece_type| squares(position.of(color, piece_type)))
+    .map(|index| {
+        let (rank, file) = square_position(index);
+        let distance = (rank as i32 - king.0 as i32)
+            .abs()
+            .max((file as i32 - king.1 as i32).abs());
+        (7 - distance) * STYLE_BONUS
+    })
+    .sum()
+}
+
+/// Score of checkmating now, in centipawns. Mates further away score a ply less for each ply.
+pub const CHECKMATE: i32 = 100_000;
+
+/// Whether a search score is a forced mate rather than a material balance.
+pub fn is_mate(score: i32) -> bool {
+    score.abs() > CHECKMATE - 1000
+}
+
+/// How a search rates a position after searching it to some depth.
+#[derive(Clone)]
+pub struct SearchResult {
+    pub depth: u32,
+    /// In centipawns from the point of view of the side to move
+    pub score: i32,
+    /// The best line of play found, starting with the best move
+    pub pv: Vec<Move>,
+}
+
+/// An alpha-beta search, remembering the best move found in each position to try it first when
+/// the position is searched again a ply deeper.
+struct Search<'a> {
+    stop: &'a AtomicBool,
+    /// When to give up, if the search is short of time
+    deadline: Option<Instant>,
+    nodes: u64,
+    best_moves: HashMap<u64, Move>,
+    /// The side whose style the positions are judged by
+    player: PieceColor,
+    style: PlayStyle,
+    /// Most centipawns a position's score is moved by at random
+    noise: i32,
+    /// Picks the random amount for each position, so it stays the same within a search
+    noise_seed: u64,
+}
+
+impl<'a> Search<'a> {
+    /// A search that judges positions on material alone.
+    fn new(stop: &'a AtomicBool) -> Self {
+        Self {
+            stop,
+            deadline: None,
+            nodes: 0,
+            best_moves: HashMap::new(),
+            player: PieceColor::White,
+            style: PlayStyle::Balanced,
+            noise: 0,
+            noise_seed: 0,
+        }
+    }
+
+    fn should_stop(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+            || matches!(self.deadline, Some(deadline) if Instant::now() >= deadline)
+    }
+
+    /// Scores a position for the side to move, in centipawns.
+    fn evaluate(&self, position: &Position) -> i32 {
+        let us = position.side_to_move;
+        let mut score = evaluate(us, position) * PAWN;
+
+        let bonus = style_bonus(self.style, self.player, position);
+        score += if us == self.player { bonus } else { -bonus };
+
+        if self.noise > 0 {
+            let hash = (position.key ^ self.noise_seed).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            score += (hash >> 33) as i32 % (2 * self.noise + 1) - self.noise;
+        }
+        score
+    }
+
+    /// Puts the best move from earlier searches first, then captures of the most valuable pieces.
+    fn order(&self, position: &Position, moves: &mut [Move]) {
+        let best = self.best_moves.get(&position.key).copied();
+        moves.sort_by_key(|mv| {
+            if Some(*mv) == best {
+                i32::MIN
+            } else {
+                -position.captured(*mv).map_or(-1, piece_value)
+            }
+        });
+    }
+
+    /// Scores a position for the side to move, filling in `pv` with the best line found, or
+    /// gives up with None once asked to stop.
+    fn negamax(
+        &mut self,
+        position: &Position,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> Option<i32> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.should_stop() {
+            return None;
+        }
+
+        let mut moves = position.legal_moves();
+        if moves.is_empty() {
+            return Some(if position.is_in_check() {
+                ply - CHECKMATE
+            } else {
+                0
+            });
+        }
+        if depth == 0 {
+            return Some(self.quiesce(position, alpha, beta));
+        }
+
+        self.order(position, &mut moves);
+        for mv in moves {
+            let mut after = *position;
+            after.play(mv);
+
+            let mut line = Vec::new();
+            let score = -self.negamax(&after, depth - 1, ply + 1, -beta, -alpha, &mut line)?;
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.extend(line);
+                self.best_moves.insert(position.key, mv);
+                if score >= beta {
+                    break;
+                }
+            }
+        }
+
+        Some(alpha)
+    }
+
+    /// Plays out captures until the position is quiet, so a search doesn't stop in the middle of
+    /// an exchange.
+    fn quiesce(&mut self, position: &Position, mut alpha: i32, beta: i32) -> i32 {
+        let standing = self.evaluate(position);
+        if standing >= beta {
+            return beta;
+        }
+        alpha = alpha.max(standing);
+
+        let mut captures = position
+            .legal_moves()
+            .into_iter()
+            .filter(|mv| position.captured(*mv).is_some())
+            .collect::<Vec<_>>();
+        self.order(position, &mut captures);
+        for mv in captures {
+            let mut after = *position;
+            after.play(mv);
+
+            let score = -self.quiesce(&after, -beta, -alpha);
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+}
+
+/// Searches a ply deeper each time until `max_depth`, or until `stop` is set, passing on the
+/// result of each depth as it's finished.
+pub fn analyse(
+    position: &Position,
+    max_depth: u32,
+    stop: &AtomicBool,
+    on_result: impl FnMut(SearchResult),
+) {
+    deepen(&mut Search::new(stop), position, max_depth, on_result);
+}
+
+fn deepen(
+    search: &mut Search,
+    position: &Position,
+    max_depth: u32,
+    mut on_result: impl FnMut(SearchResult),
+) {
+    for depth in 1..=max_depth {
+        let mut pv = Vec::new();
+        let score = match search.negamax(position, depth, 0, -CHECKMATE, CHECKMATE, &mut pv) {
+            Some(score) => score,
+            None => return,
+        };
+        on_result(SearchResult { depth, score, pv });
+
+        // Searching deeper won't find a quicker mate than one within the depth searched
+        if is_mate(score) && CHECKMATE - score.abs() <= depth as i32 {
+            return;
+        }
+    }
+}
+
+/// Searches to `depth`, or until `stop` is set, returning the deepest result found.
+pub fn search(position: &Position, depth: u32, stop: &AtomicBool) -> Option<SearchResult> {
+    let mut deepest = None;
+    analyse(position, depth, stop, |result| deepest = Some(result));
+    deepest
+}
+
+/// Picks a move the way `personality` plays: searching as deep and for as long as it allows,
+/// judging positions with its style and noise, and now and then settling for a worse move.
+pub fn think(position: &Position, personality: &Personality, stop: &AtomicBool) -> Option<Move> {
+    let mut rng = rand::thread_rng();
+    let mut search = Search {
+        deadline: Some(Instant::now() + Duration::from_secs_f32(personality.think_time)),
+        player: position.side_to_move,
+        style: personality.style,
+        noise: personality.noise,
+        noise_seed: rng.gen(),
+        ..Search::new(stop)
+    };
+    let mut best = None;
+    deepen(&mut search, position, personality.depth, |result| {
+        best = result.pv.first().copied()
+    });
+
+    if rng.gen::<f32>() < personality.blunder_chance {
+        // Look a ply ahead and take any other move that doesn't throw too much away
+        let scores = position
+            .legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut after = *position;
+                after.play(mv);
+                (mv, -search.quiesce(&after, -CHECKMATE, CHECKMATE))
+            })
+            .collect::<Vec<_>>();
+        let top = scores.iter().map(|(_, score)| *score).max()?;
+        let worse = scores
+            .iter()
+            .filter(|(mv, score)| Some(*mv) != best && *score >= top - BLUNDER_MARGIN)
+            .map(|(mv, _)| *mv)
+            .collect::<Vec<_>>();
+        if let Some(mv) = worse.choose(&mut rng) {
+            return Some(*mv);
+        }
+    }
+
+    // Out of time before finishing even a ply, any legal move will do
+    best.or_else(|| position.legal_moves().first().copied())
+}
+
+/// The AI's next move, worked out on another thread so the board keeps drawing while it thinks.
+#[derive(Default)]
+struct Thinking {
+    reply: Option<Mutex<Receiver<Move>>>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Thinking {
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.reply = None;
+    }
+}
+
+fn ai_move(
+    turn: ChangedRes<PlayerTurn>,
+    settings: Res<GameSettings>,
+    history: Res<MoveHistory>,
+    book: Res<OpeningBook>,
+    tablebases: Res<Tablebases>,
+    mut thinking: ResMut<Thinking>,
+    mut move_events: ResMut<Events<MoveEvent>>,
+) {
+    thinking.stop();
+
+    // Only play for the side the human isn't playing
+    if settings.mode != GameMode::HumanVsAi || turn.0 == settings.human_color {
+        return;
+    }
+
+    // Play from the book while the game is still in it, and perfectly once the tablebases
+    // know the position, unless the difficulty calls for mistakes in the endgame
+    let personality = Personality::new(settings.difficulty, settings.style);
+    let position = history.bitboards();
+    let book_move = if settings.use_book {
+        book.choose(&position)
+    } else {
+        None
+    };
+    let tablebase_move = if personality.use_tablebases {
+        tablebases.best_move(&position)
+    } else {
+        None
+    };
+    if let Some(mv) = book_move.or(tablebase_move) {
+        move_events.send(MoveEvent(mv));
+        return;
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, reply) = channel();
+    let thread_stop = stop.clone();
+    std::thread::spawn(move || {
+        if let Some(mv) = think(&position, &personality, &thread_stop) {
+            let _ = sender.send(mv);
+        }
+    });
+    thinking.reply = Some(Mutex::new(reply));
+    thinking.stop = Some(stop);
+}
+
+/// Plays the AI's move once it's done thinking.
+fn play_reply(mut thinking: ResMut<Thinking>, mut move_events: ResMut<Events<MoveEvent>>) {
+    let mv = match &thinking.reply {
+        Some(reply) => reply.lock().unwrap().try_recv().ok(),
+        None => return,
+    };
+    if let Some(mv) = mv {
+        thinking.stop();
+        move_events.send(MoveEvent(mv));
+    }
+}
+
+fn stop_thinking(mut thinking: ResMut<Thinking>) {
+    thinking.stop();
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Thinking>()
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, ai_move.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, play_reply.system())
+            .on_state_exit(APP_STATE_STAGE, AppState::Playing, stop_thinking.system());
+    }
+}
@@ -0,0 +1,185 @@
+//! Live analysis of the position on the board, searched deeper and deeper on a background thread
+//! for as long as the position is shown.
+
+use crate::{
+    ai::{self, SearchResult},
+    bitboard::Position,
+    board::{moves_on_board, GameSettings, MoveHistory},
+    pieces::Piece,
+    replay::ReplayCursor,
+    uci::UciEngine,
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+
+/// How deep the built-in search goes before it stops on its own.
+const MAX_DEPTH: u32 = 32;
+
+/// A position to analyse until `stop` is set.
+struct Job {
+    position: Position,
+    stop: Arc<AtomicBool>,
+}
+
+/// The thread positions are analysed on, one at a time, with the built-in search or a UCI
+/// engine. Results are sent back with the key of the position they're for.
+struct Worker {
+    jobs: Mutex<Sender<Job>>,
+    results: Mutex<Receiver<(u64, SearchResult)>>,
+}
+
+impl Worker {
+    fn spawn(engine_path: Option<String>) -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (result_sender, results) = channel();
+
+        std::thread::spawn(move || {
+            // The built-in search stands in for an engine that won't start or stops working
+            let mut engine = engine_path.and_then(|path| match UciEngine::start(&path) {
+                Ok(engine) => Some(engine),
+                Err(err) => {
+                    eprintln!("Could not start engine {}: {}", path, err);
+                    None
+                }
+            });
+
+            while let Ok(job) = job_receiver.recv() {
+                // Skip the positions that were left while this one was being analysed
+                let job = job_receiver.try_iter().last().unwrap_or(job);
+                let key = job.position.key;
+                let send = |result| {
+                    let _ = result_sender.send((key, result));
+                };
+
+                match engine.as_mut() {
+                    Some(uci) => {
+                        if let Err(err) = uci.analyse(&job.position, &job.stop, send) {
+                            eprintln!("Engine stopped working: {}", err);
+                            engine = None;
+                        }
+                    }
+                    None => ai::analyse(&job.position, MAX_DEPTH, &job.stop, send),
+                }
+            }
+        });
+
+        Self {
+            jobs: Mutex::new(jobs),
+            results: Mutex::new(results),
+        }
+    }
+}
+
+/// The analysis of the position on the board, while analysis is turned on.
+#[derive(Default)]
+pub struct Analysis {
+    /// The position being analysed, as bitboards and as pieces to write the PV out from
+    pub position: Option<(Position, Vec<Piece>)>,
+    /// The deepest search finished so far
+    pub result: Option<SearchResult>,
+    /// Counts the results received, so each is only written out once
+    pub revision: u32,
+    stop: Option<Arc<AtomicBool>>,
+    /// Started with the first position analysed
+    worker: Option<Worker>,
+}
+
+impl Analysis {
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.position = None;
+        self.result = None;
+    }
+}
+
+/// Starts analysing the position on the board whenever it changes.
+fn follow_position(
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    settings: Res<GameSettings>,
+    mut followed: Local<Option<(usize, usize)>>,
+    mut analysis: ResMut<Analysis>,
+) {
+    if !settings.show_analysis {
+        analysis.stop();
+        return;
+    }
+
+    // The moves on the board and the moves played only stay the same while the position does
+    let count = moves_on_board(&state, &history, &cursor);
+    let counts = (count, history.0.len());
+    if *followed == Some(counts) && analysis.position.is_some() {
+        return;
+    }
+    *followed = Some(counts);
+
+    let position = history.bitboards_after(count);
+    if let Some((analysed, _)) = &analysis.position {
+        if analysed.key == position.key {
+            return;
+        }
+    }
+
+    analysis.stop();
+    let stop = Arc::new(AtomicBool::new(false));
+    let worker = analysis
+        .worker
+        .get_or_insert_with(|| Worker::spawn(settings.engine_path.clone()));
+    let job = Job {
+        position,
+        stop: stop.clone(),
+    };
+    if worker.jobs.lock().unwrap().send(job).is_ok() {
+        analysis.stop = Some(stop);
+        analysis.position = Some((position, history.position_after(count)));
+    }
+}
+
+/// Keeps the latest result for the position being analysed.
+fn receive_results(mut analysis: ResMut<Analysis>) {
+    let analysis = &mut *analysis;
+    let (worker, (position, _)) = match (&analysis.worker, &analysis.position) {
+        (Some(worker), Some(position)) => (worker, position),
+        _ => return,
+    };
+
+    // Results for positions that have since been left are dropped
+    let key = position.key;
+    if let Some((_, result)) = worker
+        .results
+        .lock()
+        .unwrap()
+        .try_iter()
+        .filter(|(result_key, _)| *result_key == key)
+        .last()
+    {
+        analysis.result = Some(result);
+        analysis.revision += 1;
+    }
+}
+
+fn stop_analysis(mut analysis: ResMut<Analysis>) {
+    analysis.stop();
+}
+
+pub struct AnalysisPlugin;
+
+impl Plugin for AnalysisPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Analysis>()
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, follow_position.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, receive_results.system())
+            .on_state_exit(APP_STATE_STAGE, AppState::Playing, stop_analysis.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Replay, follow_position.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Replay, receive_results.system())
+            .on_state_exit(APP_STATE_STAGE, AppState::Replay, stop_analysis.system());
+    }
+}
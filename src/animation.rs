@@ -0,0 +1,203 @@
+use crate::{
+    board::{GameSettings, Taken},
+    pieces::{Piece, PieceColor, PieceType},
+};
+use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+/// How high knights hop over the pieces in their way.
+const KNIGHT_ARC_HEIGHT: f32 = 0.6;
+/// How much longer a captured piece takes to topple than a move takes.
+const CAPTURE_DURATION_FACTOR: f32 = 1.5;
+/// How far a captured piece sinks into the board as it falls.
+const CAPTURE_SINK_DEPTH: f32 = 0.3;
+
+/// Sent when a piece lands on its square after being moved.
+pub struct PieceAnimationFinished {
+    pub entity: Entity,
+    pub piece: Piece,
+}
+
+/// A piece travelling from one square to another.
+struct PieceAnimation {
+    from: Vec3,
+    to: Vec3,
+    elapsed: f32,
+    duration: f32,
+    arc_height: f32,
+}
+
+impl PieceAnimation {
+    fn position(&self) -> Vec3 {
+        let progress = (self.elapsed / self.duration).min(1.);
+        let eased = ease_in_out(progress);
+
+        // A parabola peaking halfway, for pieces jumping rather than sliding
+        let height = self.arc_height * 4. * progress * (1. - progress);
+        self.from + (self.to - self.from) * eased + Vec3::unit_y() * height
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Cubic easing, starting and ending slowly.
+fn ease_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        4. * t * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(3) / 2.
+    }
+}
+
+fn square_center(piece: &Piece) -> Vec3 {
+    Vec3::new(piece.x as f32, 0., piece.y as f32)
+}
+
+/// Starts animating pieces whose square has changed since they were last drawn there.
+fn start_animations(
+    commands: &mut Commands,
+    settings: Res<GameSettings>,
+    mut events: ResMut<Events<PieceAnimationFinished>>,
+    mut query: Query<(Entity, &Piece, &mut Transform), Without<PieceAnimation>>,
+) {
+    for (entity, piece, mut transform) in query.iter_mut() {
+        let to = square_center(piece);
+        if transform.translation == to {
+            continue;
+        }
+
+        if settings.move_duration <= 0. {
+            transform.translation = to;
+            events.send(PieceAnimationFinished {
+                entity,
+                piece: *piece,
+            });
+            continue;
+        }
+
+        commands.insert_one(
+            entity,
+            PieceAnimation {
+                from: transform.translation,
+                to,
+                elapsed: 0.,
+                duration: settings.move_duration,
+                arc_height: if piece.piece_type == PieceType::Knight {
+                    KNIGHT_ARC_HEIGHT
+                } else {
+                    0.
+                },
+            },
+        );
+    }
+}
+
+fn animate_pieces(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut events: ResMut<Events<PieceAnimationFinished>>,
+    mut query: Query<(Entity, &Piece, &mut Transform, &mut PieceAnimation)>,
+) {
+    for (entity, piece, mut transform, mut animation) in query.iter_mut() {
+        // The piece was moved again before landing, so head for its new square instead
+        let to = square_center(piece);
+        if animation.to != to {
+            animation.from = transform.translation;
+            animation.to = to;
+            animation.elapsed = 0.;
+        }
+
+        animation.elapsed += time.delta_seconds();
+        transform.translation = animation.position();
+
+        if animation.is_finished() {
+            // Land exactly on the square's center
+            transform.translation = to;
+            commands.remove_one::<PieceAnimation>(entity);
+            events.send(PieceAnimationFinished {
+                entity,
+                piece: *piece,
+            });
+        }
+    }
+}
+
+/// A captured piece toppling over and shrinking away before it's despawned.
+struct CaptureAnimation {
+    /// The side that made the capture, whose way the piece falls away from
+    captured_by: PieceColor,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// Starts toppling captured pieces once the capturing piece lands on their square.
+fn start_captures(
+    commands: &mut Commands,
+    settings: Res<GameSettings>,
+    mut event_reader: Local<EventReader<PieceAnimationFinished>>,
+    events: Res<Events<PieceAnimationFinished>>,
+    query: Query<(Entity, &Transform), (With<Taken>, Without<CaptureAnimation>)>,
+) {
+    for event in event_reader.iter(&events) {
+        let square = square_center(&event.piece);
+
+        for (entity, transform) in query.iter() {
+            if entity == event.entity || transform.translation != square {
+                continue;
+            }
+
+            if settings.move_duration <= 0. {
+                commands.despawn_recursive(entity);
+                continue;
+            }
+
+            commands.insert_one(
+                entity,
+                CaptureAnimation {
+                    captured_by: event.piece.color,
+                    elapsed: 0.,
+                    duration: settings.move_duration * CAPTURE_DURATION_FACTOR,
+                },
+            );
+        }
+    }
+}
+
+fn animate_captures(
+    commands: &mut Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Transform, &mut CaptureAnimation)>,
+) {
+    for (entity, mut transform, mut animation) in query.iter_mut() {
+        animation.elapsed += time.delta_seconds();
+        if animation.elapsed >= animation.duration {
+            commands.despawn_recursive(entity);
+            continue;
+        }
+
+        let progress = ease_in_out(animation.elapsed / animation.duration);
+
+        // Fall towards the far side of the capturing player
+        let angle = match animation.captured_by {
+            PieceColor::White => -FRAC_PI_2,
+            PieceColor::Black => FRAC_PI_2,
+        } * progress;
+        transform.rotation = Quat::from_rotation_z(angle);
+        transform.translation.y = -CAPTURE_SINK_DEPTH * progress;
+        transform.scale = Vec3::one() * (1. - progress);
+    }
+}
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<PieceAnimationFinished>()
+            .add_system(start_animations.system())
+            .add_system(animate_pieces.system())
+            .add_system(start_captures.system())
+            .add_system(animate_captures.system());
+    }
+}
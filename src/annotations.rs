@@ -0,0 +1,402 @@
+use crate::{
+    board::{moves_on_board, MoveHistory, Square},
+    pieces::square_name,
+    replay::ReplayCursor,
+    AppState, APP_STATE_STAGE,
+};
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, pipeline::PrimitiveTopology},
+};
+use bevy_mod_picking::{Group, PickState};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Heights above the board, so circles sit on the squares and arrows on the circles.
+const CIRCLE_HEIGHT: f32 = 0.01;
+const ARROW_HEIGHT: f32 = 0.02;
+
+const CIRCLE_INNER_RADIUS: f32 = 0.38;
+const CIRCLE_OUTER_RADIUS: f32 = 0.46;
+const CIRCLE_SEGMENTS: u32 = 32;
+
+const ARROW_SHAFT_WIDTH: f32 = 0.15;
+const ARROW_HEAD_WIDTH: f32 = 0.4;
+const ARROW_HEAD_LENGTH: f32 = 0.35;
+
+/// Colors as picked with modifier keys: none, shift, alt, or both.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnnotationColor {
+    Green,
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl AnnotationColor {
+    /// The letter used for the color in `[%csl]` and `[%cal]` commands.
+    fn letter(self) -> char {
+        match self {
+            AnnotationColor::Green => 'G',
+            AnnotationColor::Red => 'R',
+            AnnotationColor::Blue => 'B',
+            AnnotationColor::Yellow => 'Y',
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Annotation {
+    Circle {
+        square: (u8, u8),
+        color: AnnotationColor,
+    },
+    Arrow {
+        from: (u8, u8),
+        to: (u8, u8),
+        color: AnnotationColor,
+    },
+}
+
+impl Annotation {
+    fn color(&self) -> AnnotationColor {
+        match *self {
+            Annotation::Circle { color, .. } | Annotation::Arrow { color, .. } => color,
+        }
+    }
+
+    /// Whether both mark the same square, or join the same squares, whatever their color.
+    fn covers_same_squares(&self, other: &Annotation) -> bool {
+        match (*self, *other) {
+            (Annotation::Circle { square, .. }, Annotation::Circle { square: other, .. }) => {
+                square == other
+            }
+            (
+                Annotation::Arrow { from, to, .. },
+                Annotation::Arrow {
+                    from: other_from,
+                    to: other_to,
+                    ..
+                },
+            ) => from == other_from && to == other_to,
+            _ => false,
+        }
+    }
+}
+
+/// The circles and arrows drawn on each position, keyed by the number of moves played to reach it.
+#[derive(Default)]
+pub struct Annotations {
+    by_ply: HashMap<usize, Vec<Annotation>>,
+    /// Bumped on every change, so the meshes know when to be redrawn.
+    revision: u32,
+}
+
+impl Annotations {
+    pub fn at(&self, ply: usize) -> &[Annotation] {
+        self.by_ply
+            .get(&ply)
+            .map_or(&[], |annotations| annotations.as_slice())
+    }
+
+    /// Adds the annotation, or removes it if it's already there. Drawing over one in another
+    /// color replaces it.
+    pub fn toggle(&mut self, ply: usize, annotation: Annotation) {
+        let annotations = self.by_ply.entry(ply).or_default();
+        if let Some(index) = annotations
+            .iter()
+            .position(|other| other.covers_same_squares(&annotation))
+        {
+            let removed = annotations.remove(index);
+            if removed.color() == annotation.color() {
+                self.revision += 1;
+                return;
+            }
+        }
+
+        annotations.push(annotation);
+        self.revision += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.by_ply.clear();
+        self.revision += 1;
+    }
+
+    /// The position's annotations as PGN comment commands, e.g. `[%csl Ge4][%cal Re2e4]`.
+    pub fn pgn_commands(&self, ply: usize) -> Option<String> {
+        let mut circles = Vec::new();
+        let mut arrows = Vec::new();
+        for annotation in self.at(ply) {
+            match *annotation {
+                Annotation::Circle { square, color } => {
+                    circles.push(format!("{}{}", color.letter(), square_name(square)))
+                }
+                Annotation::Arrow { from, to, color } => arrows.push(format!(
+                    "{}{}{}",
+                    color.letter(),
+                    square_name(from),
+                    square_name(to)
+                )),
+            }
+        }
+
+        let mut commands = String::new();
+        if !circles.is_empty() {
+            commands.push_str(&format!("[%csl {}]", circles.join(",")));
+        }
+        if !arrows.is_empty() {
+            commands.push_str(&format!("[%cal {}]", arrows.join(",")));
+        }
+
+        if commands.is_empty() {
+            None
+        } else {
+            Some(commands)
+        }
+    }
+}
+
+struct AnnotationMaterials {
+    green: Handle<StandardMaterial>,
+    red: Handle<StandardMaterial>,
+    blue: Handle<StandardMaterial>,
+    yellow: Handle<StandardMaterial>,
+    circle: Handle<Mesh>,
+}
+
+impl FromResources for AnnotationMaterials {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut materials = resources.get_mut::<Assets<StandardMaterial>>().unwrap();
+        let mut meshes = resources.get_mut::<Assets<Mesh>>().unwrap();
+        let mut material = |color| {
+            materials.add(StandardMaterial {
+                albedo: color,
+                shaded: false,
+                ..Default::default()
+            })
+        };
+
+        AnnotationMaterials {
+            green: material(Color::rgba(0.1, 0.6, 0.1, 0.8)),
+            red: material(Color::rgba(0.8, 0.1, 0.1, 0.8)),
+            blue: material(Color::rgba(0.1, 0.3, 0.8, 0.8)),
+            yellow: material(Color::rgba(0.9, 0.7, 0.1, 0.8)),
+            circle: meshes.add(circle_mesh()),
+        }
+    }
+}
+
+impl AnnotationMaterials {
+    fn get(&self, color: AnnotationColor) -> Handle<StandardMaterial> {
+        match color {
+            AnnotationColor::Green => self.green.clone(),
+            AnnotationColor::Red => self.red.clone(),
+            AnnotationColor::Blue => self.blue.clone(),
+            AnnotationColor::Yellow => self.yellow.clone(),
+        }
+    }
+}
+
+/// Marks the meshes drawing annotations.
+struct AnnotationMesh;
+
+/// Builds a flat mesh from points on the board plane, given as `(x, z)`, facing up.
+fn flat_mesh(points: Vec<(f32, f32)>, triangles: Vec<[u32; 3]>) -> Mesh {
+    let mut indices = Vec::new();
+    for [a, b, c] in triangles {
+        let (pa, pb, pc) = (points[a as usize], points[b as usize], points[c as usize]);
+
+        // Wind every triangle counter-clockwise seen from above, so it isn't culled
+        let facing_up = (pb.1 - pa.1) * (pc.0 - pa.0) - (pb.0 - pa.0) * (pc.1 - pa.1) > 0.;
+        if facing_up {
+            indices.extend_from_slice(&[a, b, c]);
+        } else {
+            indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        points
+            .iter()
+            .map(|&(x, z)| [x, 0., z])
+            .collect::<Vec<[f32; 3]>>(),
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 1., 0.]; points.len()]);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; points.len()]);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// A ring around the origin.
+fn circle_mesh() -> Mesh {
+    let mut points = Vec::new();
+    let mut triangles = Vec::new();
+    for i in 0..CIRCLE_SEGMENTS {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2. * PI;
+        let (sin, cos) = angle.sin_cos();
+        points.push((cos * CIRCLE_INNER_RADIUS, sin * CIRCLE_INNER_RADIUS));
+        points.push((cos * CIRCLE_OUTER_RADIUS, sin * CIRCLE_OUTER_RADIUS));
+
+        let inner = 2 * i;
+        let outer = inner + 1;
+        let next_inner = 2 * ((i + 1) % CIRCLE_SEGMENTS);
+        let next_outer = next_inner + 1;
+        triangles.push([inner, outer, next_outer]);
+        triangles.push([inner, next_outer, next_inner]);
+    }
+
+    flat_mesh(points, triangles)
+}
+
+/// An arrow from the center of one square to the center of another.
+fn arrow_mesh(from: (u8, u8), to: (u8, u8)) -> Mesh {
+    let start = Vec2::new(from.0 as f32, from.1 as f32);
+    let end = Vec2::new(to.0 as f32, to.1 as f32);
+    let direction = (end - start).normalize();
+    let side = Vec2::new(-direction.y, direction.x);
+    let head_start = end - direction * ARROW_HEAD_LENGTH;
+
+    let points = [
+        start + side * ARROW_SHAFT_WIDTH / 2.,
+        start - side * ARROW_SHAFT_WIDTH / 2.,
+        head_start - side * ARROW_SHAFT_WIDTH / 2.,
+        head_start + side * ARROW_SHAFT_WIDTH / 2.,
+        head_start + side * ARROW_HEAD_WIDTH / 2.,
+        head_start - side * ARROW_HEAD_WIDTH / 2.,
+        end,
+    ];
+
+    flat_mesh(
+        points.iter().map(|point| (point.x, point.y)).collect(),
+        vec![[0, 1, 2], [0, 2, 3], [4, 5, 6]],
+    )
+}
+
+/// Right-click marks a square, right-drag draws an arrow. Holding shift, alt or both picks
+/// red, blue or yellow instead of green.
+fn annotate(
+    pick_state: Res<PickState>,
+    mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    mut annotations: ResMut<Annotations>,
+    mut drag_start: Local<Option<(u8, u8)>>,
+    squares_query: Query<&Square>,
+) {
+    let hovered_square = pick_state
+        .top(Group::default())
+        .and_then(|(entity, _intersection)| squares_query.get(*entity).ok())
+        .map(|square| (square.x, square.y));
+
+    if mouse_input.just_pressed(MouseButton::Right) {
+        *drag_start = hovered_square;
+    }
+    if !mouse_input.just_released(MouseButton::Right) {
+        return;
+    }
+
+    let (from, to) = if let (Some(from), Some(to)) = (drag_start.take(), hovered_square) {
+        (from, to)
+    } else {
+        return;
+    };
+
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+    let alt = keyboard_input.pressed(KeyCode::LAlt) || keyboard_input.pressed(KeyCode::RAlt);
+    let color = match (shift, alt) {
+        (false, false) => AnnotationColor::Green,
+        (true, false) => AnnotationColor::Red,
+        (false, true) => AnnotationColor::Blue,
+        (true, true) => AnnotationColor::Yellow,
+    };
+
+    let annotation = if from == to {
+        Annotation::Circle {
+            square: from,
+            color,
+        }
+    } else {
+        Annotation::Arrow { from, to, color }
+    };
+    annotations.toggle(moves_on_board(&state, &history, &cursor), annotation);
+}
+
+/// Redraws the annotations whenever they change or another position is shown.
+fn show_annotations(
+    commands: &mut Commands,
+    annotations: Res<Annotations>,
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    materials: Res<AnnotationMaterials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut shown: Local<Option<(usize, u32)>>,
+    query: Query<Entity, With<AnnotationMesh>>,
+) {
+    let ply = moves_on_board(&state, &history, &cursor);
+    if *shown == Some((ply, annotations.revision)) {
+        return;
+    }
+    *shown = Some((ply, annotations.revision));
+
+    for entity in query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    for annotation in annotations.at(ply) {
+        let (mesh, translation) = match *annotation {
+            Annotation::Circle { square, .. } => (
+                materials.circle.clone(),
+                Vec3::new(square.0 as f32, CIRCLE_HEIGHT, square.1 as f32),
+            ),
+            Annotation::Arrow { from, to, .. } => (
+                meshes.add(arrow_mesh(from, to)),
+                Vec3::new(0., ARROW_HEIGHT, 0.),
+            ),
+        };
+
+        commands
+            .spawn(PbrBundle {
+                mesh,
+                material: materials.get(annotation.color()),
+                visible: Visible {
+                    is_transparent: true,
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(translation),
+                ..Default::default()
+            })
+            .with(AnnotationMesh);
+    }
+}
+
+fn clear_annotations(mut annotations: ResMut<Annotations>) {
+    annotations.clear();
+}
+
+pub struct AnnotationsPlugin;
+
+impl Plugin for AnnotationsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Annotations>()
+            .init_resource::<AnnotationMaterials>()
+            .add_system(show_annotations.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                clear_annotations.system(),
+            )
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, annotate.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::Replay,
+                clear_annotations.system(),
+            )
+            .on_state_update(APP_STATE_STAGE, AppState::Replay, annotate.system());
+    }
+}
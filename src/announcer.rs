@@ -0,0 +1,204 @@
+use crate::{
+    board::{moves_on_board, GameResult, MoveHistory},
+    keyboard::MoveEntry,
+    pieces::{is_in_check, legal_moves, square_name, Move, Piece, PieceColor, PieceType},
+    replay::ReplayCursor,
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
+
+/// A game event in words, for screen readers and anything else listening.
+pub struct Announcement(pub String);
+
+/// Somewhere announcements are delivered, e.g. a screen reader's speech queue.
+pub trait AnnouncementSink: Send + Sync + 'static {
+    fn announce(&mut self, text: &str);
+}
+
+/// Prints each announcement on its own line.
+pub struct StdoutSink;
+
+impl AnnouncementSink for StdoutSink {
+    fn announce(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// Where announcements go. Printed to stdout unless the sinks are replaced.
+pub struct Announcer {
+    pub sinks: Vec<Box<dyn AnnouncementSink>>,
+}
+
+impl Default for Announcer {
+    fn default() -> Self {
+        Self {
+            sinks: vec![Box::new(StdoutSink)],
+        }
+    }
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "White",
+        PieceColor::Black => "Black",
+    }
+}
+
+fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::King => "king",
+        PieceType::Queen => "queen",
+        PieceType::Rook => "rook",
+        PieceType::Bishop => "bishop",
+        PieceType::Knight => "knight",
+        PieceType::Pawn => "pawn",
+    }
+}
+
+fn piece_on(position: (u8, u8), pieces: &[Piece]) -> Option<&Piece> {
+    pieces.iter().find(|piece| (piece.x, piece.y) == position)
+}
+
+/// Describes a move as it would be said, e.g. "White knight from g1 to f3, check".
+fn describe_move(mv: Move, before: &[Piece], after: &[Piece]) -> String {
+    let piece = match piece_on(mv.from, before) {
+        Some(piece) => piece,
+        None => {
+            return format!(
+                "Move from {} to {}",
+                square_name(mv.from),
+                square_name(mv.to)
+            )
+        }
+    };
+
+    let mut text = format!(
+        "{} {} from {} to {}",
+        color_name(piece.color),
+        piece_name(piece.piece_type),
+        square_name(mv.from),
+        square_name(mv.to)
+    );
+
+    if let Some(captured) = piece_on(mv.to, before) {
+        text.push_str(&format!(
+            ", takes {} {}",
+            color_name(captured.color).to_lowercase(),
+            piece_name(captured.piece_type)
+        ));
+    }
+
+    let opponent = piece.color.opposite();
+    if is_in_check(opponent, after) {
+        if legal_moves(opponent, after).is_empty() {
+            text.push_str(", checkmate");
+        } else {
+            text.push_str(", check");
+        }
+    }
+
+    text
+}
+
+/// Reads out every rank from the 8th down, e.g. "Rank 1: a1 white rook, e1 white king."
+fn describe_position(pieces: &[Piece]) -> String {
+    let mut ranks = Vec::new();
+    for rank in (0..8).rev() {
+        let squares = (0..8)
+            .filter_map(|file| {
+                piece_on((rank, file), pieces).map(|piece| {
+                    format!(
+                        "{} {} {}",
+                        square_name((rank, file)),
+                        color_name(piece.color).to_lowercase(),
+                        piece_name(piece.piece_type)
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let contents = if squares.is_empty() {
+            "empty".to_string()
+        } else {
+            squares.join(", ")
+        };
+        ranks.push(format!("Rank {}: {}.", rank + 1, contents));
+    }
+
+    ranks.join(" ")
+}
+
+/// Announces each move as it's played, or stepped through in a replay.
+fn announce_moves(
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    mut announced: Local<usize>,
+    mut announcements: ResMut<Events<Announcement>>,
+) {
+    let shown_moves = moves_on_board(&state, &history, &cursor);
+
+    // Only a single step forward is a move; anything else is a new game or a jump in a replay
+    if shown_moves == *announced + 1 {
+        let before = history.position_after(*announced);
+        let after = history.position_after(shown_moves);
+        announcements.send(Announcement(describe_move(
+            history.0[*announced],
+            &before,
+            &after,
+        )));
+    }
+    *announced = shown_moves;
+}
+
+fn announce_result(result: Res<GameResult>, mut announcements: ResMut<Events<Announcement>>) {
+    announcements.send(Announcement(result.description()));
+}
+
+/// Reads out the whole position with R.
+fn read_position(
+    keyboard_input: Res<Input<KeyCode>>,
+    entry: Res<MoveEntry>,
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    mut announcements: ResMut<Events<Announcement>>,
+) {
+    // R is part of a move while one is being typed
+    if entry.typing || !keyboard_input.just_pressed(KeyCode::R) {
+        return;
+    }
+
+    let pieces = history.position_after(moves_on_board(&state, &history, &cursor));
+    announcements.send(Announcement(describe_position(&pieces)));
+}
+
+fn deliver_announcements(
+    mut event_reader: Local<EventReader<Announcement>>,
+    events: Res<Events<Announcement>>,
+    mut announcer: ResMut<Announcer>,
+) {
+    for event in event_reader.iter(&events) {
+        for sink in announcer.sinks.iter_mut() {
+            sink.announce(&event.0);
+        }
+    }
+}
+
+pub struct AnnouncerPlugin;
+
+impl Plugin for AnnouncerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Announcer>()
+            .add_event::<Announcement>()
+            .add_system(announce_moves.system())
+            .add_system(deliver_announcements.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, read_position.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Replay, read_position.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::GameOver,
+                announce_result.system(),
+            );
+    }
+}
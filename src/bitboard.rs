@@ -0,0 +1,779 @@
+//! Bitboards, where each square of the board is one bit of a `u64`: `rank * 8 + file`, with a1 as
+//! bit 0. Sliding pieces look their attacks up in magic bitboard tables, built once on first use.
+
+use crate::{
+    pieces::{Move, Piece, PieceColor, PieceType},
+    zobrist::{
+        castling_key, en_passant_key, piece_key, turn_key, BLACK_LONG, BLACK_SHORT, WHITE_LONG,
+        WHITE_SHORT,
+    },
+};
+use lazy_static::lazy_static;
+
+pub type Bitboard = u64;
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_STEPS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Multipliers hashing the blockers of a rook or bishop into its attack table, one per square.
+/// Found by trial, picking random sparse numbers until every set of blockers hashed to a slot
+/// holding the right attacks.
+const ROOK_MAGICS: [u64; 64] = [
+    0x1080004008801020,
+    0x0840092002C03000,
+    0x1900200010400900,
+    0x0880100008000480,
+    0x4200100420080200,
+    0x8100020100080400,
+    0x0200040110886200,
+    0x0200008040220411,
+    0x0404800084400220,
+    0x0000401000402000,
+    0x0086001081220440,
+    0x0408800800100280,
+    0x000A001201040820,
+    0x8848800200840080,
+    0x4001000100040200,
+    0x0442000102105084,
+    0x9080010020804100,
+    0x0040404000201009,
+    0x0000808010002009,
+    0x2200090021D00100,
+    0x0008008008040080,
+    0x0004004002010040,
+    0x0011040008015042,
+    0x00000A0001768104,
+    0x0000800080204009,
+    0x2010004140002001,
+    0x9800200280100080,
+    0x1000100080080080,
+    0x0442000A00049020,
+    0x2100040080020080,
+    0x0800120400900148,
+    0x0010040A00128541,
+    0x2800804000800030,
+    0x1010002000400041,
+    0x4000200011004100,
+    0x0610008410800800,
+    0x0400802402800800,
+    0xC100020080800400,
+    0x0002000802000401,
+    0x0182085882000401,
+    0x0220204000808000,
+    0x2860100040024022,
+    0x0001002004110040,
+    0x99101042000A0020,
+    0x0004080004008080,
+    0x0010040002008080,
+    0x2012004881020004,
+    0x8300842444820011,
+    0x0088403882010200,
+    0x0820400080210100,
+    0x0110910040A00300,
+    0x0801100280080480,
+    0x0242009008200600,
+    0x1002000489500200,
+    0x0040800200010080,
+    0x0091800041000080,
+    0x0000209300488001,
+    0x04C1002414824001,
+    0x020020000B001041,
+    0x7000100004200901,
+    0x8002002004100802,
+    0x30010002084C0007,
+    0x0888221800813004,
+    0x4000002840840112,
+];
+const BISHOP_MAGICS: [u64; 64] = [
+    0xA010041108003100,
+    0x006082020A002900,
+    0x6810010619200000,
+    0x08281A0520000408,
+    0x0001104001000400,
+    0x0018901008048400,
+    0x00040A0210245280,
+    0x000200210808A402,
+    0x9140048410821200,
+    0x0800091010820041,
+    0x20504804832202C0,
+    0x0100091401081000,
+    0x8021011140000012,
+    0x0810020804450400,
+    0x208B0542109008A2,
+    0x0080084A08040204,
+    0x0040E2A80811244C,
+    0x2505022008008108,
+    0x0430220100420040,
+    0x010A040420220040,
+    0x1105000290400000,
+    0x0093001200822120,
+    0x4000A62048043004,
+    0x280120048A015004,
+    0x006090002A020814,
+    0x44042000240800D0,
+    0x01102800040A4400,
+    0x1004080080220040,
+    0x0001001011004024,
+    0x0010044000805040,
+    0x0914041200820100,
+    0x0004821012821480,
+    0x0024040500C05021,
+    0x0088611002080200,
+    0x0116080A00040020,
+    0x4000020080080080,
+    0x2450450140840040,
+    0x0000880201484100,
+    0x0222020404020092,
+    0x8081110600002E00,
+    0x2842101105000801,
+    0x1100809008001025,
+    0x00020202221C0400,
+    0x0422014022009020,
+    0x0210046102100C00,
+    0xC004008082029102,
+    0x00AA461801101200,
+    0x0404080080201108,
+    0x020542108C205002,
+    0x0410544804100100,
+    0x0040910841100000,
+    0x0400200042021100,
+    0x00004204850400C0,
+    0x0200100410A42102,
+    0x1040020801210102,
+    0x0805040410420000,
+    0x2884804130100200,
+    0x800C262201242000,
+    0x1058000194108800,
+    0x0014221054420204,
+    0x0104000012A02200,
+    0x0200881003300100,
+    0x0140400202840100,
+    0x0402020801010201,
+];
+
+pub fn square_index(position: (u8, u8)) -> u8 {
+    position.0 * 8 + position.1
+}
+
+pub fn square_position(index: u8) -> (u8, u8) {
+    (index / 8, index % 8)
+}
+
+fn bit(index: u8) -> Bitboard {
+    1 << index
+}
+
+/// The squares of the set bits, lowest first.
+pub fn squares(mut board: Bitboard) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if board == 0 {
+            return None;
+        }
+        let index = board.trailing_zeros() as u8;
+        board &= board - 1;
+        Some(index)
+    })
+}
+
+/// Squares one of the given steps away from `index`, each taken once.
+fn step_attacks(index: u8, steps: &[(i8, i8)]) -> Bitboard {
+    let (rank, file) = square_position(index);
+    steps
+        .iter()
+        .map(|(rank_step, file_step)| (rank as i8 + rank_step, file as i8 + file_step))
+        .filter(|(rank, file)| (0..8).contains(rank) && (0..8).contains(file))
+        .fold(0, |board, (rank, file)| {
+            board | bit(square_index((rank as u8, file as u8)))
+        })
+}
+
+/// Squares reached sliding from `index` until the edge or a blocker, which is included.
+///
+/// With `mask` set, it instead gives the squares whose blockers matter: the ray short of the edge.
+fn slide(index: u8, directions: &[(i8, i8)], blockers: Bitboard, mask: bool) -> Bitboard {
+    let (rank, file) = square_position(index);
+    let on_board = |rank: i8, file: i8| (0..8).contains(&rank) && (0..8).contains(&file);
+
+    let mut board = 0;
+    for (rank_step, file_step) in directions {
+        let (mut rank, mut file) = (rank as i8 + rank_step, file as i8 + file_step);
+        while on_board(rank, file) {
+            if mask && !on_board(rank + rank_step, file + file_step) {
+                break;
+            }
+
+            let square = bit(square_index((rank as u8, file as u8)));
+            board |= square;
+            if blockers & square != 0 {
+                break;
+            }
+            rank += rank_step;
+            file += file_step;
+        }
+    }
+    board
+}
+
+/// The attack table of a sliding piece on one square.
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    attacks: Vec<Bitboard>,
+}
+
+impl Magic {
+    fn new(index: u8, directions: &[(i8, i8)], magic: u64) -> Self {
+        let mask = slide(index, directions, 0, true);
+        let shift = 64 - mask.count_ones();
+        let mut attacks = vec![0; 1 << mask.count_ones()];
+
+        // Walk every subset of the mask as blockers
+        let mut blockers: Bitboard = 0;
+        loop {
+            let slot = (blockers.wrapping_mul(magic) >> shift) as usize;
+            let attack = slide(index, directions, blockers, false);
+            // Blockers may share a slot only if they leave the same squares attacked. No slot is
+            // ever meant to be empty, as a slider always attacks at least one square.
+            debug_assert!(
+                attacks[slot] == 0 || attacks[slot] == attack,
+                "magic {:#018x} for square {} mixes up two attack sets",
+                magic,
+                index
+            );
+            attacks[slot] = attack;
+
+            blockers = blockers.wrapping_sub(mask) & mask;
+            if blockers == 0 {
+                break;
+            }
+        }
+
+        Magic {
+            mask,
+            magic,
+            shift,
+            attacks,
+        }
+    }
+
+    fn attacks(&self, occupied: Bitboard) -> Bitboard {
+        self.attacks[((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize]
+    }
+}
+
+struct Tables {
+    knight: Vec<Bitboard>,
+    king: Vec<Bitboard>,
+    /// Squares attacked by a pawn, by color and then square
+    pawn: [Vec<Bitboard>; 2],
+    rook: Vec<Magic>,
+    bishop: Vec<Magic>,
+    /// Squares strictly between two squares on a line, or none if they don't share one
+    between: Vec<Vec<Bitboard>>,
+}
+
+impl Tables {
+    fn new() -> Self {
+        let all_squares = 0..64u8;
+        let pawn_attacks = |forward: i8| {
+            all_squares
+                .clone()
+                .map(|index| step_attacks(index, &[(forward, 1), (forward, -1)]))
+                .collect()
+        };
+
+        let between = all_squares
+            .clone()
+            .map(|from| {
+                all_squares
+                    .clone()
+                    .map(|to| {
+                        ROOK_DIRECTIONS
+                            .iter()
+                            .chain(BISHOP_DIRECTIONS.iter())
+                            .map(|direction| slide(from, &[*direction], bit(to), false))
+                            .find(|ray| ray & bit(to) != 0)
+                            .map_or(0, |ray| ray & !bit(to))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Tables {
+            knight: all_squares
+                .clone()
+                .map(|index| step_attacks(index, &KNIGHT_STEPS))
+                .collect(),
+            king: all_squares
+                .clone()
+                .map(|index| step_attacks(index, &KING_STEPS))
+                .collect(),
+            pawn: [pawn_attacks(1), pawn_attacks(-1)],
+            rook: all_squares
+                .clone()
+                .map(|index| Magic::new(index, &ROOK_DIRECTIONS, ROOK_MAGICS[index as usize]))
+                .collect(),
+            bishop: all_squares
+                .map(|index| Magic::new(index, &BISHOP_DIRECTIONS, BISHOP_MAGICS[index as usize]))
+                .collect(),
+            between,
+        }
+    }
+}
+
+lazy_static! {
+    static ref TABLES: Tables = Tables::new();
+}
+
+fn rook_attacks(index: u8, occupied: Bitboard) -> Bitboard {
+    TABLES.rook[index as usize].attacks(occupied)
+}
+
+fn bishop_attacks(index: u8, occupied: Bitboard) -> Bitboard {
+    TABLES.bishop[index as usize].attacks(occupied)
+}
+
+fn color_index(color: PieceColor) -> usize {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    }
+}
+
+const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+fn type_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+/// A position as bitboards, with the side to move, following the same rules as the game.
+///
+/// Positions are small enough to copy, so a move is taken back by keeping the copy from before it.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Position {
+    /// Squares of each kind of piece, by color and then type in `PIECE_TYPES` order
+    boards: [[Bitboard; 6]; 2],
+    /// Squares of each color's pieces
+    occupied: [Bitboard; 2],
+    pub side_to_move: PieceColor,
+    /// Castling rights, as the flags in `zobrist`, lost once a king or rook leaves its home square
+    castling: u8,
+    /// The file of a pawn that just moved two squares, if an enemy pawn stands beside it
+    en_passant: Option<u8>,
+    /// The Zobrist key, kept up to date as moves are played
+    pub key: u64,
+}
+
+/// The castling rights given up when a piece moves from or is taken on each home square.
+const CASTLING_SQUARES: [(u8, u8); 6] = [
+    (4, WHITE_SHORT | WHITE_LONG),
+    (7, WHITE_SHORT),
+    (0, WHITE_LONG),
+    (60, BLACK_SHORT | BLACK_LONG),
+    (63, BLACK_SHORT),
+    (56, BLACK_LONG),
+];
+
+impl Position {
+    pub fn from_pieces(pieces: &[Piece], side_to_move: PieceColor) -> Self {
+        let mut position = Position {
+            boards: [[0; 6]; 2],
+            occupied: [0; 2],
+            side_to_move,
+            castling: 0,
+            en_passant: None,
+            key: turn_key(side_to_move),
+        };
+        for piece in pieces {
+            let index = square_index((piece.x, piece.y));
+            position.boards[color_index(piece.color)][type_index(piece.piece_type)] |= bit(index);
+            position.occupied[color_index(piece.color)] |= bit(index);
+            position.key ^= piece_key(piece.color, piece.piece_type, index);
+        }
+
+        // Castling isn't part of the game, but a king and rook at home keep the right to it in
+        // Polyglot keys, so it's tracked to match them
+        let rooks = [
+            (PieceColor::White, 4, 7, WHITE_SHORT),
+            (PieceColor::White, 4, 0, WHITE_LONG),
+            (PieceColor::Black, 60, 63, BLACK_SHORT),
+            (PieceColor::Black, 60, 56, BLACK_LONG),
+        ];
+        for &(color, king, rook, right) in rooks.iter() {
+            if position.of(color, PieceType::King) & bit(king) != 0
+                && position.of(color, PieceType::Rook) & bit(rook) != 0
+            {
+                position.castling |= right;
+            }
+        }
+        position.key ^= castling_key(position.castling);
+
+        position
+    }
+
+    /// Squares of the given color's pieces of the given type.
+    pub fn of(&self, color: PieceColor, piece_type: PieceType) -> Bitboard {
+        self.boards[color_index(color)][type_index(piece_type)]
+    }
+
+    fn all_occupied(&self) -> Bitboard {
+        self.occupied[0] | self.occupied[1]
+    }
+
+    /// Pieces of `color` attacking `index`, with the board occupied as given.
+    fn attackers(&self, index: u8, color: PieceColor, occupied: Bitboard) -> Bitboard {
+        let them = &self.boards[color_index(color)];
+        let queens = them[type_index(PieceType::Queen)];
+
+        // A pawn attacks the squares that a pawn of the other color would attack it from
+        (TABLES.pawn[color_index(color.opposite())][index as usize]
+            & them[type_index(PieceType::Pawn)])
+            | (TABLES.knight[index as usize] & them[type_index(PieceType::Knight)])
+            | (TABLES.king[index as usize] & them[type_index(PieceType::King)])
+            | (bishop_attacks(index, occupied) & (them[type_index(PieceType::Bishop)] | queens))
+            | (rook_attacks(index, occupied) & (them[type_index(PieceType::Rook)] | queens))
+    }
+
+    fn king_square(&self, color: PieceColor) -> Option<u8> {
+        squares(self.of(color, PieceType::King)).next()
+    }
+
+    /// Whether the side to move's king is attacked.
+    pub fn is_in_check(&self) -> bool {
+        let us = self.side_to_move;
+        match self.king_square(us) {
+            Some(king) => self.attackers(king, us.opposite(), self.all_occupied()) != 0,
+            None => false,
+        }
+    }
+
+    /// The color and type of the piece on a square, if there is one.
+    fn piece_on(&self, index: u8) -> Option<(PieceColor, PieceType)> {
+        [PieceColor::White, PieceColor::Black]
+            .iter()
+            .flat_map(|&color| {
+                PIECE_TYPES
+                    .iter()
+                    .map(move |&piece_type| (color, piece_type))
+            })
+            .find(|&(color, piece_type)| self.of(color, piece_type) & bit(index) != 0)
+    }
+
+    /// The type of the piece a move takes, if it takes one.
+    pub fn captured(&self, mv: Move) -> Option<PieceType> {
+        self.piece_on(square_index(mv.to))
+            .map(|(_, piece_type)| piece_type)
+    }
+
+    /// Plays a move, capturing whatever stands on its destination, and passes the turn.
+    pub fn play(&mut self, mv: Move) {
+        let from = square_index(mv.from);
+        let to = square_index(mv.to);
+        let moved = self.piece_on(from);
+
+        if let Some((color, piece_type)) = self.piece_on(to) {
+            self.boards[color_index(color)][type_index(piece_type)] ^= bit(to);
+            self.key ^= piece_key(color, piece_type, to);
+        }
+        if let Some((color, piece_type)) = moved {
+            self.boards[color_index(color)][type_index(piece_type)] ^= bit(from) | bit(to);
+            self.key ^= piece_key(color, piece_type, from) ^ piece_key(color, piece_type, to);
+        }
+        for color in 0..2 {
+            self.occupied[color] = self.boards[color].iter().fold(0, |all, board| all | board);
+        }
+
+        let castling = CASTLING_SQUARES
+            .iter()
+            .filter(|(square, _)| *square == from || *square == to)
+            .fold(self.castling, |rights, (_, lost)| rights & !lost);
+        self.key ^= castling_key(self.castling ^ castling);
+        self.castling = castling;
+
+        let us = self.side_to_move;
+        let them = us.opposite();
+
+        // Polyglot only counts the en passant file when a pawn is there to take it
+        if let Some(file) = self.en_passant.take() {
+            self.key ^= en_passant_key(file);
+        }
+        let is_double_push = (from as i8 - to as i8).abs() == 16;
+        if moved == Some((us, PieceType::Pawn)) && is_double_push {
+            let file = to % 8;
+            let beside = squares(TABLES.king[to as usize])
+                .filter(|square| square / 8 == to / 8)
+                .fold(0, |board, square| board | bit(square));
+            if beside & self.of(them, PieceType::Pawn) != 0 {
+                self.en_passant = Some(file);
+                self.key ^= en_passant_key(file);
+            }
+        }
+
+        self.key ^= turn_key(us) ^ turn_key(them);
+        self.side_to_move = them;
+    }
+
+    /// Every legal move of the side to move, in a single pass: checks and pins are worked out up
+    /// front, so no move has to be tried to see if it leaves the king in check.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let us = self.side_to_move;
+        let them = us.opposite();
+        let ours = self.occupied[color_index(us)];
+        let theirs = self.occupied[color_index(them)];
+        let occupied = ours | theirs;
+
+        let push = |moves: &mut Vec<Move>, from: u8, targets: Bitboard| {
+            for to in squares(targets) {
+                moves.push(Move {
+                    from: square_position(from),
+                    to: square_position(to),
+                });
+            }
+        };
+
+        let king = match self.king_square(us) {
+            Some(king) => king,
+            None => return moves,
+        };
+
+        // The king can't step along the line of a slider checking it, so it's lifted off the board
+        let without_king = occupied & !bit(king);
+        let king_targets = squares(TABLES.king[king as usize] & !ours)
+            .filter(|to| self.attackers(*to, them, without_king) == 0)
+            .fold(0, |board, to| board | bit(to));
+        push(&mut moves, king, king_targets);
+
+        // In double check only the king can move; in single check others must take or block
+        let checkers = self.attackers(king, them, occupied);
+        let check_mask = match checkers.count_ones() {
+            0 => !0,
+            1 => checkers | TABLES.between[king as usize][checkers.trailing_zeros() as usize],
+            _ => return moves,
+        };
+
+        // A piece alone between the king and an enemy slider may only move along that line
+        let mut pin_rays = [!0; 64];
+        let their_queens = self.of(them, PieceType::Queen);
+        let snipers = (rook_attacks(king, theirs)
+            & (self.of(them, PieceType::Rook) | their_queens))
+            | (bishop_attacks(king, theirs) & (self.of(them, PieceType::Bishop) | their_queens));
+        for sniper in squares(snipers) {
+            let between = TABLES.between[king as usize][sniper as usize];
+            let blockers = between & occupied;
+            if blockers.count_ones() == 1 && blockers & ours != 0 {
+                pin_rays[blockers.trailing_zeros() as usize] = between | bit(sniper);
+            }
+        }
+
+        let (forward, start_rank): (i8, u8) = match us {
+            PieceColor::White => (8, 1),
+            PieceColor::Black => (-8, 6),
+        };
+        let empty = !occupied;
+
+        for &piece_type in PIECE_TYPES.iter().filter(|&&t| t != PieceType::King) {
+            for from in squares(self.of(us, piece_type)) {
+                let attacks = match piece_type {
+                    PieceType::Pawn => {
+                        let mut targets = TABLES.pawn[color_index(us)][from as usize] & theirs;
+                        let one = from as i8 + forward;
+                        if (0..64).contains(&one) && empty & bit(one as u8) != 0 {
+                            targets |= bit(one as u8);
+                            let two = one + forward;
+                            if from / 8 == start_rank && empty & bit(two as u8) != 0 {
+                                targets |= bit(two as u8);
+                            }
+                        }
+                        targets
+                    }
+                    PieceType::Knight => TABLES.knight[from as usize] & !ours,
+                    PieceType::Bishop => bishop_attacks(from, occupied) & !ours,
+                    PieceType::Rook => rook_attacks(from, occupied) & !ours,
+                    PieceType::Queen => {
+                        (bishop_attacks(from, occupied) | rook_attacks(from, occupied)) & !ours
+                    }
+                    PieceType::King => 0,
+                };
+
+                push(
+                    &mut moves,
+                    from,
+                    attacks & check_mask & pin_rays[from as usize],
+                );
+            }
+        }
+
+        moves
+    }
+
+    /// How many pieces are on the board, kings included.
+    pub fn piece_count(&self) -> u32 {
+        self.all_occupied().count_ones()
+    }
+
+    /// The key without the castling rights and en passant file, which the game doesn't have, so
+    /// positions that look the same on the board count as the same when checking for repetition.
+    pub fn repetition_key(&self) -> u64 {
+        let en_passant = self.en_passant.map_or(0, en_passant_key);
+        self.key ^ castling_key(self.castling) ^ en_passant
+    }
+
+    /// The position in Forsyth-Edwards Notation. The game has no castling or en passant, and
+    /// positions don't keep the move counters, so those fields are always `- - 0 1`.
+    pub fn fen(&self) -> String {
+        let mut ranks = Vec::new();
+        for rank in (0..8).rev() {
+            let mut placement = String::new();
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_on(square_index((rank, file))) {
+                    Some((color, piece_type)) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = match piece_type {
+                            PieceType::Pawn => 'p',
+                            PieceType::Knight => 'n',
+                            PieceType::Bishop => 'b',
+                            PieceType::Rook => 'r',
+                            PieceType::Queen => 'q',
+                            PieceType::King => 'k',
+                        };
+                        placement.push(match color {
+                            PieceColor::White => letter.to_ascii_uppercase(),
+                            PieceColor::Black => letter,
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            ranks.push(placement);
+        }
+
+        let side = match self.side_to_move {
+            PieceColor::White => 'w',
+            PieceColor::Black => 'b',
+        };
+        format!("{} {} - - 0 1", ranks.join("/"), side)
+    }
+
+    /// Counts the move sequences `depth` moves deep, to check and time move generation.
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        moves
+            .into_iter()
+            .map(|mv| {
+                let mut after = *self;
+                after.play(mv);
+                after.perft(depth - 1)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::{parse_fen, starting_position};
+
+    #[test]
+    fn perft_from_start() {
+        let position = Position::from_pieces(&starting_position(), PieceColor::White);
+        for (depth, nodes) in [20, 400, 8902, 197281].iter().enumerate() {
+            assert_eq!(position.perft(depth as u32 + 1), *nodes);
+        }
+    }
+
+    /// The usual count is 4865609, less the 258 en passant captures the game doesn't have.
+    #[test]
+    fn perft_from_start_at_depth_five() {
+        let position = Position::from_pieces(&starting_position(), PieceColor::White);
+        assert_eq!(position.perft(5), 4865351);
+    }
+
+    /// Both sides have castled and no pawn can be taken en passant within four plies, so the
+    /// usual counts hold under the game's rules.
+    #[test]
+    fn perft_from_middlegame() {
+        let fen = "r2q1rk1/ppp2ppp/2np1n2/2b1p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2Q1RK1 w - - 0 1";
+        let (pieces, side_to_move) = parse_fen(fen).unwrap();
+        let position = Position::from_pieces(&pieces, side_to_move);
+        for (depth, nodes) in [40, 1569, 61185, 2389862].iter().enumerate() {
+            assert_eq!(position.perft(depth as u32 + 1), *nodes);
+        }
+    }
+
+    /// Every set of blockers on every square, with pieces beyond the mask too, looks up the
+    /// attacks that sliding out to the blockers finds.
+    #[test]
+    fn magics_match_sliding() {
+        let tables = [
+            (&TABLES.rook, ROOK_DIRECTIONS),
+            (&TABLES.bishop, BISHOP_DIRECTIONS),
+        ];
+        for (magics, directions) in tables.iter() {
+            for (index, magic) in magics.iter().enumerate() {
+                let index = index as u8;
+                let mut blockers: Bitboard = 0;
+                loop {
+                    let expected = slide(index, directions, blockers, false);
+                    assert_eq!(magic.attacks(blockers), expected, "square {}", index);
+                    assert_eq!(magic.attacks(blockers | !magic.mask), expected);
+
+                    blockers = blockers.wrapping_sub(magic.mask) & magic.mask;
+                    if blockers == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
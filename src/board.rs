@@ -1,5 +1,23 @@
-use crate::pieces::{Piece, PieceColor, PieceType};
-use bevy::{app::AppExit, prelude::*};
+use crate::{
+    annotations::Annotations,
+    bitboard::Position,
+    book::DEFAULT_BOOK_PATH,
+    difficulty::{Difficulty, PlayStyle},
+    hint::Hints,
+    keyboard::KeyboardCursor,
+    openings::Openings,
+    pgn,
+    pieces::{
+        apply_move, is_in_check, is_insufficient_material, legal_moves, starting_position, Move,
+        Piece, PieceColor, PieceType,
+    },
+    replay::ReplayCursor,
+    review::GameReview,
+    tablebase::DEFAULT_TABLEBASE_PATH,
+    theme::Theme,
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
 use bevy_mod_picking::{Group, PickState, PickableMesh};
 
 pub struct Square {
@@ -9,13 +27,13 @@ pub struct Square {
 
 impl Square {
     fn is_white(&self) -> bool {
-        (self.x + self.y + 1) % 2 == 0
+        (self.x + self.y + 1).is_multiple_of(2)
     }
 }
 
 #[derive(Default)]
-struct SelectedSquare {
-    entity: Option<Entity>,
+pub struct SelectedSquare {
+    pub entity: Option<Entity>,
 }
 
 #[derive(Default)]
@@ -25,6 +43,7 @@ struct SelectedPiece {
 
 fn create_board(
     commands: &mut Commands,
+    theme: Res<Theme>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
@@ -38,9 +57,9 @@ fn create_board(
                 .spawn(PbrBundle {
                     mesh: mesh.clone(),
                     material: if square.is_white() {
-                        materials.add(Color::rgb(1., 0.9, 0.9).into())
+                        materials.add(Color::from(theme.light_square).into())
                     } else {
-                        materials.add(Color::rgb(0., 0.1, 0.1).into())
+                        materials.add(Color::from(theme.dark_square).into())
                     },
                     transform: Transform::from_translation(Vec3::new(x as f32, 0., y as f32)),
                     ..Default::default()
@@ -51,9 +70,42 @@ fn create_board(
     }
 }
 
+/// How many of the history's moves have been played on the board as it's shown.
+pub fn moves_on_board(
+    state: &State<AppState>,
+    history: &MoveHistory,
+    cursor: &ReplayCursor,
+) -> usize {
+    // In a replay only the moves up to the cursor are on the board
+    if *state.current() == AppState::Replay {
+        cursor.0
+    } else {
+        history.0.len()
+    }
+}
+
+/// How strongly the last move's squares are tinted with the theme's last-move color.
+const LAST_MOVE_TINT: f32 = 0.5;
+
+/// The squares marked by the position on the board, worked out again only once it changes.
+#[derive(Default)]
+struct MarkedSquares {
+    /// The moves on the board and the moves played when they were worked out
+    counts: Option<(usize, usize)>,
+    checked_king: Option<(u8, u8)>,
+    last_move: Option<Move>,
+}
+
 fn color_squares(
     pick_state: Res<PickState>,
+    theme: Res<Theme>,
     selected_square: Res<SelectedSquare>,
+    history: Res<MoveHistory>,
+    state: Res<State<AppState>>,
+    cursor: Res<ReplayCursor>,
+    keyboard_cursor: Res<KeyboardCursor>,
+    hints: Res<Hints>,
+    mut marked: Local<MarkedSquares>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     query: Query<(Entity, &Square, &Handle<StandardMaterial>)>,
 ) {
@@ -63,20 +115,69 @@ fn color_squares(
         None
     };
 
+    let shown_moves = moves_on_board(&state, &history, &cursor);
+    let hint = hints
+        .suggestion(shown_moves)
+        .filter(|_| *state.current() == AppState::Playing);
+
+    let counts = (shown_moves, history.0.len());
+    if marked.counts != Some(counts) {
+        let pieces = history.position_after(shown_moves);
+        let side_to_move = if shown_moves.is_multiple_of(2) {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+        *marked = MarkedSquares {
+            counts: Some(counts),
+            checked_king: pieces
+                .iter()
+                .find(|piece| piece.color == side_to_move && piece.piece_type == PieceType::King)
+                .filter(|_| is_in_check(side_to_move, &pieces))
+                .map(|king| (king.x, king.y)),
+            last_move: shown_moves.checked_sub(1).map(|index| history.0[index]),
+        };
+    }
+
     for (entity, square, material_handle) in query.iter() {
-        // Get the material
-        let material = materials.get_mut(material_handle).unwrap();
-
-        // Change the color of the selected square
-        material.albedo = if Some(entity) == selected_square.entity {
-            Color::rgb(0.8, 0.6, 0.1)
-        } else if Some(entity) == top_entity {
-            Color::rgb(0.8, 0.3, 0.3)
-        } else if square.is_white() {
-            Color::rgb(1., 0.9, 0.9)
+        let base = if square.is_white() {
+            theme.light_square
+        } else {
+            theme.dark_square
+        };
+        let position = (square.x, square.y);
+
+        // Selection wins over hovering, which wins over check, which wins over a hint, which wins
+        // over the last move. The keyboard cursor counts as hovering.
+        let color = if Some(entity) == selected_square.entity {
+            theme.selected_square
+        } else if Some(entity) == top_entity || Some(position) == keyboard_cursor.square {
+            theme.hovered_square
+        } else if Some(position) == marked.checked_king {
+            theme.check_square
+        } else if hint
+            .iter()
+            .any(|mv| mv.from == position || mv.to == position)
+        {
+            theme.hint_square
+        } else if marked
+            .last_move
+            .iter()
+            .any(|mv| mv.from == position || mv.to == position)
+        {
+            base.mix(theme.last_move_square, LAST_MOVE_TINT)
         } else {
-            Color::rgb(0., 0.1, 0.1)
+            base
         };
+
+        // Changing a material uploads it again, so leave the ones that already match alone
+        let albedo = color.into();
+        let current = materials
+            .get(material_handle)
+            .map(|material| material.albedo);
+        if current != Some(albedo) {
+            materials.get_mut(material_handle).unwrap().albedo = albedo;
+        }
     }
 }
 
@@ -108,9 +209,15 @@ fn select_piece(
     selected_square: ChangedRes<SelectedSquare>,
     mut selected_piece: ResMut<SelectedPiece>,
     turn: Res<PlayerTurn>,
+    settings: Res<GameSettings>,
     squares_query: Query<&Square>,
     pieces_query: Query<(Entity, &Piece)>,
 ) {
+    // Don't let the player pick up the AI's pieces
+    if settings.mode == GameMode::HumanVsAi && turn.0 != settings.human_color {
+        return;
+    }
+
     // Only care if a square is selected
     let square_entity = if let Some(entity) = selected_square.entity {
         entity
@@ -137,12 +244,11 @@ fn select_piece(
 }
 
 fn move_piece(
-    commands: &mut Commands,
     selected_square: ChangedRes<SelectedSquare>,
     selected_piece: Res<SelectedPiece>,
-    mut turn: ResMut<PlayerTurn>,
     squares_query: Query<&Square>,
-    mut pieces_query: Query<(Entity, &mut Piece)>,
+    pieces_query: Query<&Piece>,
+    mut move_events: ResMut<Events<MoveEvent>>,
     mut reset_selected_event: ResMut<Events<ResetSelectedEvent>>,
 ) {
     // Only care if a square is selected
@@ -160,43 +266,72 @@ fn move_piece(
     };
 
     if let Some(selected_piece_entity) = selected_piece.entity {
+        // Ask to move the selected piece to the selected square
+        if let Ok(piece) = pieces_query.get(selected_piece_entity) {
+            move_events.send(MoveEvent(Move {
+                from: (piece.x, piece.y),
+                to: (square.x, square.y),
+            }));
+        }
+
+        reset_selected_event.send(ResetSelectedEvent);
+    }
+}
+
+/// Sent by anything that wants to play a move for the side to move.
+pub struct MoveEvent(pub Move);
+
+fn make_move(
+    commands: &mut Commands,
+    mut event_reader: Local<EventReader<MoveEvent>>,
+    events: Res<Events<MoveEvent>>,
+    mut turn: ResMut<PlayerTurn>,
+    mut history: ResMut<MoveHistory>,
+    mut result: ResMut<GameResult>,
+    mut state: ResMut<State<AppState>>,
+    mut pieces_query: Query<(Entity, &mut Piece)>,
+) {
+    for event in event_reader.iter(&events) {
+        // The game may already have ended this frame, e.g. by resignation
+        if result.termination.is_some() {
+            return;
+        }
+
+        let mv = event.0;
         let pieces_vec = pieces_query
             .iter_mut()
             .map(|(_, piece)| *piece)
             .collect::<Vec<Piece>>();
-        let pieces_entity_vec = pieces_query
-            .iter_mut()
-            .map(|(entity, piece)| (entity, *piece))
-            .collect::<Vec<(Entity, Piece)>>();
 
-        // Find the piece, if it exists
-        let mut piece = if let Ok((_, piece)) = pieces_query.get_mut(selected_piece_entity) {
-            piece
-        } else {
-            return;
-        };
+        // Only the side to move's pieces have legal moves
+        if !legal_moves(turn.0, &pieces_vec).contains(&mv) {
+            continue;
+        }
 
-        // Move the piece to the selected square, if valid
-        if piece.is_move_valid((square.x, square.y), &pieces_vec) {
-            // Capture opposing pieces, if present
-            for (other_entity, other_piece) in pieces_entity_vec {
-                if other_piece.x == square.x
-                    && other_piece.y == square.y
-                    && other_piece.color != piece.color
-                {
-                    commands.insert_one(other_entity, Taken);
-                }
+        for (entity, mut piece) in pieces_query.iter_mut() {
+            if (piece.x, piece.y) == mv.to {
+                // Capture the opposing piece. It's off the board as far as the rules go, but
+                // stays in sight until the capture has been animated.
+                commands.remove_one::<Piece>(entity);
+                commands.insert_one(entity, Taken);
+            } else if (piece.x, piece.y) == mv.from {
+                piece.x = mv.to.0;
+                piece.y = mv.to.1;
             }
+        }
 
-            // Move piece
-            piece.x = square.x;
-            piece.y = square.y;
+        history.0.push(mv);
 
-            // Change turn
-            turn.change();
+        // Game Over if the move ended the game
+        if let Some((winner, termination)) = history.termination() {
+            result.winner = winner;
+            result.termination = Some(termination);
+            state.set_next(AppState::GameOver).unwrap();
+            return;
         }
 
-        reset_selected_event.send(ResetSelectedEvent);
+        // Change turn
+        turn.change();
     }
 }
 
@@ -206,7 +341,7 @@ fn reset_selected(
     mut event_reader: Local<EventReader<ResetSelectedEvent>>,
     events: Res<Events<ResetSelectedEvent>>,
     mut selected_square: ResMut<SelectedSquare>,
-    mut selected_piece: ResMut<SelectedSquare>,
+    mut selected_piece: ResMut<SelectedPiece>,
 ) {
     for _ in event_reader.iter(&events) {
         selected_square.entity = None;
@@ -224,37 +359,309 @@ impl Default for PlayerTurn {
 
 impl PlayerTurn {
     fn change(&mut self) {
-        self.0 = match self.0 {
-            PieceColor::White => PieceColor::Black,
-            PieceColor::Black => PieceColor::White,
+        self.0 = self.0.opposite();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameMode {
+    HumanVsHuman,
+    HumanVsAi,
+}
+
+pub struct GameSettings {
+    pub mode: GameMode,
+    /// The side played by the human in human-vs-AI games, or by the first player in hot-seat games
+    pub human_color: PieceColor,
+    /// Minutes on each player's clock, or None for untimed games
+    pub time_control: Option<u32>,
+    /// Shows each square's name on the board, for learning the coordinates
+    pub show_square_names: bool,
+    /// Seconds a piece takes to travel to its new square, 0 to move instantly
+    pub move_duration: f32,
+    /// Lets the AI play moves from the opening book while the game is in it
+    pub use_book: bool,
+    /// The Polyglot opening book to play from
+    pub book_path: String,
+    /// The directory of Syzygy tablebase files
+    pub tablebase_path: String,
+    /// Shows the evaluation bar and best line while playing and replaying
+    pub show_analysis: bool,
+    /// A UCI engine to analyse with instead of the built-in search
+    pub engine_path: Option<String>,
+    /// How strongly the computer plays
+    pub difficulty: Difficulty,
+    /// What the computer aims for in its play
+    pub style: PlayStyle,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::HumanVsHuman,
+            human_color: PieceColor::White,
+            time_control: None,
+            show_square_names: false,
+            move_duration: 0.3,
+            use_book: true,
+            book_path: DEFAULT_BOOK_PATH.to_string(),
+            tablebase_path: DEFAULT_TABLEBASE_PATH.to_string(),
+            show_analysis: true,
+            engine_path: None,
+            difficulty: Difficulty::Club,
+            style: PlayStyle::Balanced,
+        }
+    }
+}
+
+impl GameSettings {
+    pub fn player_name(&self, color: PieceColor) -> &'static str {
+        match (self.mode, color == self.human_color) {
+            (GameMode::HumanVsHuman, true) => "Player 1",
+            (GameMode::HumanVsHuman, false) => "Player 2",
+            (GameMode::HumanVsAi, true) => "Human",
+            (GameMode::HumanVsAi, false) => "Computer",
         }
     }
 }
 
-struct Taken;
+/// The moves played so far in the current game.
+#[derive(Default)]
+pub struct MoveHistory(pub Vec<Move>);
+
+impl MoveHistory {
+    /// The pieces as they stand after the first `count` moves.
+    pub fn position_after(&self, count: usize) -> Vec<Piece> {
+        let mut pieces = starting_position();
+        for mv in self.0.iter().take(count) {
+            apply_move(&mut pieces, *mv);
+        }
+        pieces
+    }
 
-fn despawn_taken_pieces(
-    commands: &mut Commands,
-    mut app_exit_events: ResMut<Events<AppExit>>,
-    query: Query<(Entity, &Piece, &Taken)>,
-) {
-    for (entity, piece, _taken) in query.iter() {
-        // If the King is taken, Game Over!
-        if piece.piece_type == PieceType::King {
-            println!(
-                "{} won! Thanks for playing.",
-                match piece.color {
-                    PieceColor::White => "Black",
-                    PieceColor::Black => "White",
-                }
-            );
-            app_exit_events.send(AppExit);
+    /// The pieces as they stand after every move played so far.
+    pub fn position(&self) -> Vec<Piece> {
+        self.position_after(self.0.len())
+    }
+
+    /// The position after the first `count` moves, as bitboards.
+    pub fn bitboards_after(&self, count: usize) -> Position {
+        let mut position = Position::from_pieces(&starting_position(), PieceColor::White);
+        for mv in self.0.iter().take(count) {
+            position.play(*mv);
+        }
+        position
+    }
+
+    /// The position after every move played so far, as bitboards.
+    pub fn bitboards(&self) -> Position {
+        self.bitboards_after(self.0.len())
+    }
+
+    /// Returns the winner, if any, and how the game ended if the last move ended it.
+    pub fn termination(&self) -> Option<(Option<PieceColor>, Termination)> {
+        termination(&starting_position(), PieceColor::White, &self.0)
+    }
+}
+
+/// Returns the winner, if any, and how the game ended if the last of `moves` ended it, for a game
+/// started from `start` with `first_to_move` to play.
+pub fn termination(
+    start: &[Piece],
+    first_to_move: PieceColor,
+    moves: &[Move],
+) -> Option<(Option<PieceColor>, Termination)> {
+    let mut pieces = start.to_vec();
+    let mut position = Position::from_pieces(&pieces, first_to_move);
+    let mut keys = vec![position.repetition_key()];
+    let mut quiet_moves = 0;
+
+    for mv in moves.iter() {
+        // Captures and pawn moves reset the fifty-move count
+        let is_capture = pieces.iter().any(|piece| (piece.x, piece.y) == mv.to);
+        let is_pawn_move = pieces
+            .iter()
+            .any(|piece| (piece.x, piece.y) == mv.from && piece.piece_type == PieceType::Pawn);
+        quiet_moves = if is_capture || is_pawn_move {
+            0
+        } else {
+            quiet_moves + 1
+        };
+
+        apply_move(&mut pieces, *mv);
+        position.play(*mv);
+        keys.push(position.repetition_key());
+    }
+
+    let side_to_move = position.side_to_move;
+
+    if legal_moves(side_to_move, &pieces).is_empty() {
+        return Some(if is_in_check(side_to_move, &pieces) {
+            (Some(side_to_move.opposite()), Termination::Checkmate)
+        } else {
+            (None, Termination::Draw(DrawReason::Stalemate))
+        });
+    }
+
+    if is_insufficient_material(&pieces) {
+        return Some((None, Termination::Draw(DrawReason::InsufficientMaterial)));
+    }
+
+    if quiet_moves >= 100 {
+        return Some((None, Termination::Draw(DrawReason::FiftyMoveRule)));
+    }
+
+    // The side to move is part of the key, so only positions with the same side to move match,
+    // while castling rights and en passant, which the game doesn't have, are left out of it
+    let current = keys[keys.len() - 1];
+    let repetitions = keys.iter().filter(|key| **key == current).count();
+    if repetitions >= 3 {
+        return Some((None, Termination::Draw(DrawReason::ThreefoldRepetition)));
+    }
+
+    None
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DrawReason {
+    Stalemate,
+    ThreefoldRepetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Resignation,
+    Timeout,
+    /// An engine broke down or played an illegal move
+    Forfeit,
+    Draw(DrawReason),
+}
+
+#[derive(Default)]
+pub struct GameResult {
+    pub winner: Option<PieceColor>,
+    /// How the game ended, or None while it's still going
+    pub termination: Option<Termination>,
+}
+
+impl GameResult {
+    pub fn pgn_result(&self) -> &'static str {
+        match (self.termination, self.winner) {
+            (None, _) => "*",
+            (Some(_), Some(PieceColor::White)) => "1-0",
+            (Some(_), Some(PieceColor::Black)) => "0-1",
+            (Some(_), None) => "1/2-1/2",
         }
+    }
+
+    /// A sentence describing the result, e.g. "White wins by checkmate".
+    pub fn description(&self) -> String {
+        let reason = match self.termination {
+            None => return "Game in progress".to_string(),
+            Some(Termination::Checkmate) => "by checkmate",
+            Some(Termination::Resignation) => "by resignation",
+            Some(Termination::Timeout) => "on time",
+            Some(Termination::Forfeit) => "by forfeit",
+            Some(Termination::Draw(DrawReason::Stalemate)) => "by stalemate",
+            Some(Termination::Draw(DrawReason::ThreefoldRepetition)) => "by threefold repetition",
+            Some(Termination::Draw(DrawReason::FiftyMoveRule)) => "by the fifty-move rule",
+            Some(Termination::Draw(DrawReason::InsufficientMaterial)) => "by insufficient material",
+        };
 
-        commands.despawn_recursive(entity);
+        match self.winner {
+            Some(PieceColor::White) => format!("White wins {}", reason),
+            Some(PieceColor::Black) => format!("Black wins {}", reason),
+            None => format!("Draw {}", reason),
+        }
     }
 }
 
+/// Seconds left on each player's clock.
+#[derive(Default)]
+pub struct Clock {
+    pub white: f32,
+    pub black: f32,
+}
+
+pub const SAVE_PATH: &str = "saves/last_game.pgn";
+
+fn reset_game(
+    settings: Res<GameSettings>,
+    mut turn: ResMut<PlayerTurn>,
+    mut selected_square: ResMut<SelectedSquare>,
+    mut selected_piece: ResMut<SelectedPiece>,
+    mut history: ResMut<MoveHistory>,
+    mut result: ResMut<GameResult>,
+    mut clock: ResMut<Clock>,
+) {
+    *turn = PlayerTurn::default();
+    selected_square.entity = None;
+    selected_piece.entity = None;
+    history.0.clear();
+    *result = GameResult::default();
+
+    let seconds = settings.time_control.unwrap_or(0) as f32 * 60.;
+    clock.white = seconds;
+    clock.black = seconds;
+}
+
+fn tick_clock(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    turn: Res<PlayerTurn>,
+    mut clock: ResMut<Clock>,
+    mut result: ResMut<GameResult>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if settings.time_control.is_none() || result.termination.is_some() {
+        return;
+    }
+
+    let remaining = match turn.0 {
+        PieceColor::White => &mut clock.white,
+        PieceColor::Black => &mut clock.black,
+    };
+    *remaining -= time.delta_seconds();
+
+    // Flag fell, Game Over!
+    if *remaining <= 0. {
+        *remaining = 0.;
+        result.winner = Some(turn.0.opposite());
+        result.termination = Some(Termination::Timeout);
+        state.set_next(AppState::GameOver).unwrap();
+    }
+}
+
+/// Writes the finished game to disk so it can be replayed from the main menu. It's saved before
+/// it's reviewed, so without the review's marks.
+fn save_game(
+    history: Res<MoveHistory>,
+    result: Res<GameResult>,
+    settings: Res<GameSettings>,
+    annotations: Res<Annotations>,
+    openings: Res<Openings>,
+    hints: Res<Hints>,
+) {
+    if let Err(err) = pgn::save(
+        SAVE_PATH,
+        &history,
+        &result,
+        &settings,
+        &annotations,
+        &openings,
+        &GameReview::default(),
+        &hints,
+    ) {
+        eprintln!("Could not save game to {}: {}", SAVE_PATH, err);
+    }
+}
+
+/// A captured piece, on its way off the board.
+pub struct Taken;
+
 pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
@@ -262,13 +669,54 @@ impl Plugin for BoardPlugin {
         app.init_resource::<SelectedSquare>()
             .init_resource::<SelectedPiece>()
             .init_resource::<PlayerTurn>()
+            .init_resource::<GameSettings>()
+            .init_resource::<MoveHistory>()
+            .init_resource::<GameResult>()
+            .init_resource::<Clock>()
             .add_event::<ResetSelectedEvent>()
+            .add_event::<MoveEvent>()
             .add_startup_system(create_board.system())
             .add_system(color_squares.system())
-            .add_system(despawn_taken_pieces.system())
-            .add_system(move_piece.system())
-            .add_system(reset_selected.system())
-            .add_system(select_piece.system())
-            .add_system(select_square.system());
+            .on_state_enter(APP_STATE_STAGE, AppState::Playing, reset_game.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, make_move.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, tick_clock.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, move_piece.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, reset_selected.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, select_piece.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, select_square.system())
+            .on_state_enter(APP_STATE_STAGE, AppState::GameOver, save_game.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::parse_square;
+
+    fn moves(notation: &[&str]) -> Vec<Move> {
+        notation
+            .iter()
+            .map(|mv| Move {
+                from: parse_square(&mv[..2]).unwrap(),
+                to: parse_square(&mv[2..]).unwrap(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn king_shuffle_draws_on_third_occurrence() {
+        // The kings give up their castling rights on leaving home, but the position after
+        // 1. e4 e5 comes back twice all the same
+        let moves = moves(&[
+            "e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8", "e1e2", "e8e7", "e2e1", "e7e8",
+        ]);
+        let start = starting_position();
+
+        assert!(termination(&start, PieceColor::White, &moves[..6]).is_none());
+        assert!(termination(&start, PieceColor::White, &moves[..9]).is_none());
+        assert!(
+            termination(&start, PieceColor::White, &moves)
+                == Some((None, Termination::Draw(DrawReason::ThreefoldRepetition)))
+        );
     }
 }
@@ -0,0 +1,134 @@
+//! Polyglot opening books: `.bin` files of 16 byte entries sorted by Zobrist key, each holding a
+//! big-endian key, move, weight and learning value.
+
+use crate::{bitboard::Position, board::GameSettings, pieces::Move};
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Where the opening book is read from, unless another is given with `--book <path>`.
+pub const DEFAULT_BOOK_PATH: &str = "books/book.bin";
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Clone, Copy)]
+struct BookEntry {
+    key: u64,
+    mv: u16,
+    weight: u16,
+}
+
+/// The entries of the opening book at `GameSettings::book_path`, empty if it couldn't be read.
+#[derive(Default)]
+pub struct OpeningBook {
+    /// The file the entries were read from
+    path: String,
+    entries: Vec<BookEntry>,
+}
+
+/// Unpacks a book move, three bits each for the destination file and rank, the origin file and
+/// rank, and the promotion piece.
+fn decode_move(mv: u16) -> Option<Move> {
+    let field = |shift: u16| ((mv >> shift) & 7) as u8;
+
+    // There's no promotion in the game to play these with
+    if field(12) != 0 {
+        return None;
+    }
+
+    Some(Move {
+        from: (field(9), field(6)),
+        to: (field(3), field(0)),
+    })
+}
+
+impl OpeningBook {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(format!(
+                "{} bytes is not a whole number of book entries",
+                bytes.len()
+            ));
+        }
+
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                let mut key = [0; 8];
+                key.copy_from_slice(&entry[..8]);
+                BookEntry {
+                    key: u64::from_be_bytes(key),
+                    mv: u16::from_be_bytes([entry[8], entry[9]]),
+                    weight: u16::from_be_bytes([entry[10], entry[11]]),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            path: path.to_string(),
+            entries,
+        })
+    }
+
+    /// The book's moves in a position with their weights, leaving out the ones the game doesn't
+    /// have, like castling, and the ones weighted never to be played.
+    pub fn moves(&self, position: &Position) -> Vec<(Move, u16)> {
+        let legal_moves = position.legal_moves();
+        let start = self
+            .entries
+            .partition_point(|entry| entry.key < position.key);
+
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == position.key)
+            .filter(|entry| entry.weight > 0)
+            .filter_map(|entry| decode_move(entry.mv).map(|mv| (mv, entry.weight)))
+            .filter(|(mv, _)| legal_moves.contains(mv))
+            .collect()
+    }
+
+    /// Picks one of the book's moves at random, the more often the more weight it has.
+    pub fn choose(&self, position: &Position) -> Option<Move> {
+        let moves = self.moves(position);
+        let total = moves.iter().map(|(_, weight)| *weight as u32).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = rand::thread_rng().gen_range(0..total);
+        for (mv, weight) in moves {
+            if pick < weight as u32 {
+                return Some(mv);
+            }
+            pick -= weight as u32;
+        }
+        None
+    }
+}
+
+/// Reads the book again whenever its path is changed, e.g. on startup.
+fn load_book(settings: Res<GameSettings>, mut book: ResMut<OpeningBook>) {
+    if book.path == settings.book_path {
+        return;
+    }
+
+    *book = OpeningBook::load(&settings.book_path).unwrap_or_else(|err| {
+        eprintln!(
+            "Could not load opening book from {}: {}",
+            settings.book_path, err
+        );
+        OpeningBook {
+            path: settings.book_path.clone(),
+            entries: Vec::new(),
+        }
+    });
+}
+
+pub struct BookPlugin;
+
+impl Plugin for BookPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<OpeningBook>()
+            .add_system(load_book.system());
+    }
+}
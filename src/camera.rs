@@ -0,0 +1,395 @@
+use crate::{
+    board::{GameMode, GameSettings, PlayerTurn},
+    keyboard::MoveEntry,
+    pieces::PieceColor,
+    AppState, APP_STATE_STAGE,
+};
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::{
+        camera::{
+            ActiveCameras, Camera, CameraProjection, OrthographicProjection, PerspectiveProjection,
+        },
+        render_graph::base::camera::CAMERA_3D,
+    },
+};
+use bevy_mod_picking::PickSource;
+use std::f32::consts::{FRAC_PI_2, PI};
+
+/// How quickly the camera catches up with where it should be.
+const FOLLOW_SPEED: f32 = 6.0;
+/// Radians turned per pixel of mouse movement.
+const ORBIT_SENSITIVITY: f32 = 0.005;
+/// Board squares moved per pixel of mouse movement, at the default distance.
+const PAN_SENSITIVITY: f32 = 0.02;
+/// Distance zoomed per line of mouse wheel scrolling.
+const ZOOM_STEP: f32 = 1.5;
+
+const MIN_PITCH: f32 = 0.2;
+/// Just short of straight down, where "up" for the camera would be undefined.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+const MIN_DISTANCE: f32 = 6.0;
+const MAX_DISTANCE: f32 = 40.0;
+/// How far the point looked at may wander from the board's center.
+const MAX_PAN: f32 = 4.0;
+
+/// Marks the camera looking at the board.
+pub struct BoardCamera;
+
+/// Marks the orthographic camera drawing the board in 2D, which follows `BoardCamera` from
+/// straight above. Picking still casts its rays from `BoardCamera`, which sees the flat board
+/// the same way.
+struct FlatCamera;
+
+/// Whether the board is drawn with models in perspective, or flat with sprites from above.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RenderMode {
+    ThreeD,
+    TwoD,
+}
+
+/// Position of the camera on a sphere around the point it looks at.
+#[derive(Clone, Copy)]
+struct Orbit {
+    /// Rotation around the vertical axis, 0 looking from the bottom side.
+    yaw: f32,
+    /// Angle above the board.
+    pitch: f32,
+    distance: f32,
+    /// The point looked at, relative to the board's center.
+    focus: Vec3,
+}
+
+impl Orbit {
+    /// Looking over the bottom side's shoulder, as the game always has.
+    fn perspective() -> Self {
+        Self {
+            yaw: 0.,
+            pitch: 1.08,
+            distance: 22.7,
+            focus: Vec3::new(0.17, 0., 0.5),
+        }
+    }
+
+    fn top_down() -> Self {
+        Self {
+            yaw: 0.,
+            pitch: MAX_PITCH,
+            distance: 16.0,
+            focus: Vec3::zero(),
+        }
+    }
+
+    fn transform(&self) -> Transform {
+        let focus = board_center() + self.focus;
+        let offset = Quat::from_rotation_y(self.yaw)
+            * Vec3::new(
+                -self.distance * self.pitch.cos(),
+                self.distance * self.pitch.sin(),
+                0.,
+            );
+
+        Transform::from_translation(focus + offset).looking_at(focus, Vec3::unit_y())
+    }
+
+    /// Moves `share` of the way towards `target`.
+    fn approach(&mut self, target: &Orbit, share: f32) {
+        self.yaw += (target.yaw - self.yaw) * share;
+        self.pitch += (target.pitch - self.pitch) * share;
+        self.distance += (target.distance - self.distance) * share;
+        self.focus += (target.focus - self.focus) * share;
+    }
+
+    fn is_near(&self, target: &Orbit) -> bool {
+        (target.yaw - self.yaw).abs() < 0.001
+            && (target.pitch - self.pitch).abs() < 0.001
+            && (target.distance - self.distance).abs() < 0.01
+            && (target.focus - self.focus).length() < 0.01
+    }
+}
+
+/// Which side of the board the camera looks from, and how it has been moved around.
+pub struct BoardView {
+    /// The side that would be at the bottom of the screen without flipping.
+    pub side: PieceColor,
+    /// Shows the board from the other side of `side`.
+    pub flipped: bool,
+    /// Where the player has moved the camera to, relative to the bottom side.
+    orbit: Orbit,
+    /// Where the camera is now, easing towards the bottom side's view of `orbit`.
+    current: Orbit,
+    pub render_mode: RenderMode,
+}
+
+impl Default for BoardView {
+    fn default() -> Self {
+        Self {
+            side: PieceColor::White,
+            flipped: false,
+            orbit: Orbit::perspective(),
+            current: Orbit::perspective(),
+            render_mode: RenderMode::ThreeD,
+        }
+    }
+}
+
+impl BoardView {
+    /// The side currently at the bottom of the screen.
+    pub fn bottom(&self) -> PieceColor {
+        if self.flipped {
+            self.side.opposite()
+        } else {
+            self.side
+        }
+    }
+
+    /// Puts `color` at the bottom of the screen, whatever side is being followed.
+    pub fn show_from(&mut self, color: PieceColor) {
+        self.flipped = self.side != color;
+    }
+
+    pub fn toggle_render_mode(&mut self) {
+        self.render_mode = match self.render_mode {
+            RenderMode::ThreeD => RenderMode::TwoD,
+            RenderMode::TwoD => RenderMode::ThreeD,
+        };
+        self.orbit = self.home_orbit();
+    }
+
+    /// Where the camera goes back to when the view is reset.
+    fn home_orbit(&self) -> Orbit {
+        match self.render_mode {
+            RenderMode::ThreeD => Orbit::perspective(),
+            RenderMode::TwoD => Orbit::top_down(),
+        }
+    }
+
+    fn target(&self) -> Orbit {
+        let side_yaw = match self.bottom() {
+            PieceColor::White => 0.,
+            PieceColor::Black => PI,
+        };
+
+        Orbit {
+            yaw: side_yaw + self.orbit.yaw,
+            focus: Quat::from_rotation_y(side_yaw) * self.orbit.focus,
+            ..self.orbit
+        }
+    }
+}
+
+fn board_center() -> Vec3 {
+    Vec3::new(3.5, 0., 3.5)
+}
+
+fn spawn_camera(commands: &mut Commands) {
+    commands
+        .spawn(Camera3dBundle {
+            transform: Orbit::perspective().transform(),
+            ..Default::default()
+        })
+        .with(PickSource::default())
+        .with(BoardCamera)
+        .spawn(Camera2dBundle {
+            // Unnamed, so it's only drawn with once made the active 3D camera
+            camera: Camera::default(),
+            ..Default::default()
+        })
+        .with(FlatCamera);
+}
+
+/// The local player's side against the AI, whoever is to move in hot-seat.
+fn player_side(settings: &GameSettings, turn: PieceColor) -> PieceColor {
+    match settings.mode {
+        GameMode::HumanVsAi => settings.human_color,
+        GameMode::HumanVsHuman => turn,
+    }
+}
+
+fn orient_to_player(settings: Res<GameSettings>, mut view: ResMut<BoardView>) {
+    // Every game starts with white to move
+    view.side = player_side(&settings, PieceColor::White);
+    view.flipped = false;
+    view.orbit = view.home_orbit();
+}
+
+fn follow_turn(
+    turn: ChangedRes<PlayerTurn>,
+    settings: Res<GameSettings>,
+    mut view: ResMut<BoardView>,
+) {
+    view.side = player_side(&settings, turn.0);
+}
+
+fn orient_to_white(mut view: ResMut<BoardView>) {
+    view.side = PieceColor::White;
+    view.flipped = false;
+    view.orbit = view.home_orbit();
+}
+
+fn flip_board(
+    keyboard_input: Res<Input<KeyCode>>,
+    entry: Res<MoveEntry>,
+    mut view: ResMut<BoardView>,
+) {
+    // F is part of a move while one is being typed
+    if entry.typing {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::F) {
+        view.flipped = !view.flipped;
+    }
+}
+
+fn preset_views(
+    keyboard_input: Res<Input<KeyCode>>,
+    entry: Res<MoveEntry>,
+    mut view: ResMut<BoardView>,
+) {
+    if entry.typing {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Key1) {
+        view.show_from(PieceColor::White);
+        view.orbit = view.home_orbit();
+    }
+    if keyboard_input.just_pressed(KeyCode::Key2) {
+        view.show_from(PieceColor::Black);
+        view.orbit = view.home_orbit();
+    }
+    if keyboard_input.just_pressed(KeyCode::Key3) {
+        view.orbit = Orbit::top_down();
+    }
+    if keyboard_input.just_pressed(KeyCode::V) {
+        view.toggle_render_mode();
+    }
+}
+
+/// Orbits with the middle mouse button, pans with it while holding shift and zooms with the wheel.
+/// The 2D view only pans and zooms, as it always looks straight down.
+///
+/// The left button is left alone for picking squares, and the right one for annotations.
+fn mouse_controls(
+    mut motion_reader: Local<EventReader<MouseMotion>>,
+    mut wheel_reader: Local<EventReader<MouseWheel>>,
+    motion_events: Res<Events<MouseMotion>>,
+    wheel_events: Res<Events<MouseWheel>>,
+    mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut view: ResMut<BoardView>,
+) {
+    let mut delta = Vec2::zero();
+    for event in motion_reader.iter(&motion_events) {
+        delta += event.delta;
+    }
+
+    let mut scroll = 0.;
+    for event in wheel_reader.iter(&wheel_events) {
+        scroll += match event.unit {
+            MouseScrollUnit::Line => event.y,
+            // Roughly one line per 20 pixels
+            MouseScrollUnit::Pixel => event.y / 20.,
+        };
+    }
+
+    let can_orbit = view.render_mode == RenderMode::ThreeD;
+    let orbit = &mut view.orbit;
+    let shift = keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift);
+    if mouse_input.pressed(MouseButton::Middle) && !shift && can_orbit {
+        orbit.yaw -= delta.x * ORBIT_SENSITIVITY;
+        orbit.pitch = (orbit.pitch + delta.y * ORBIT_SENSITIVITY).clamp(MIN_PITCH, MAX_PITCH);
+    } else if mouse_input.pressed(MouseButton::Middle) {
+        // Drag the board along with the mouse, further when zoomed out
+        let scale = PAN_SENSITIVITY * orbit.distance / Orbit::perspective().distance;
+        let pan = Quat::from_rotation_y(orbit.yaw) * Vec3::new(delta.y, 0., -delta.x) * scale;
+        orbit.focus += pan;
+        orbit.focus.x = orbit.focus.x.clamp(-MAX_PAN, MAX_PAN);
+        orbit.focus.z = orbit.focus.z.clamp(-MAX_PAN, MAX_PAN);
+    }
+    orbit.distance = (orbit.distance - scroll * ZOOM_STEP).clamp(MIN_DISTANCE, MAX_DISTANCE);
+}
+
+fn move_camera(
+    time: Res<Time>,
+    mut view: ResMut<BoardView>,
+    mut query: Query<&mut Transform, With<BoardCamera>>,
+) {
+    let target = view.target();
+    if view.current.is_near(&target) {
+        return;
+    }
+
+    // Ease out, covering the same share of the remaining way every second
+    let share = 1. - (-FOLLOW_SPEED * time.delta_seconds()).exp();
+    view.current.approach(&target, share);
+
+    // Picking casts its rays from this transform, so it stays accurate as the camera moves
+    for mut transform in query.iter_mut() {
+        *transform = view.current.transform();
+    }
+}
+
+/// Draws with the orthographic camera in the 2D view, keeping it over what `BoardCamera` sees.
+fn follow_with_flat_camera(
+    view: Res<BoardView>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut shown_mode: Local<Option<RenderMode>>,
+    board_camera_query: Query<(Entity, &PerspectiveProjection), With<BoardCamera>>,
+    mut flat_camera_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Camera,
+            &mut OrthographicProjection,
+        ),
+        With<FlatCamera>,
+    >,
+) {
+    for (board_camera, perspective) in board_camera_query.iter() {
+        for (flat_camera, mut transform, mut camera, mut projection) in flat_camera_query.iter_mut()
+        {
+            if *shown_mode != Some(view.render_mode) {
+                *shown_mode = Some(view.render_mode);
+                let active = match view.render_mode {
+                    RenderMode::ThreeD => board_camera,
+                    RenderMode::TwoD => flat_camera,
+                };
+                active_cameras.set(CAMERA_3D, active);
+            }
+
+            // Show as much of the board plane as the perspective camera does at its focus
+            *transform = view.current.transform();
+            let half_height = view.current.distance * (perspective.fov / 2.).tan();
+            let half_width = half_height * perspective.aspect_ratio;
+            projection.left = -half_width;
+            projection.right = half_width;
+            projection.bottom = -half_height;
+            projection.top = half_height;
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+    }
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<BoardView>()
+            .add_startup_system(spawn_camera.system())
+            .add_system(flip_board.system())
+            .add_system(preset_views.system())
+            .add_system(mouse_controls.system())
+            .add_system(move_camera.system())
+            .add_system(follow_with_flat_camera.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                orient_to_player.system(),
+            )
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, follow_turn.system())
+            .on_state_enter(APP_STATE_STAGE, AppState::Replay, orient_to_white.system());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{
+    board::GameSettings,
+    camera::{BoardCamera, BoardView},
+    pieces::{square_name, PieceColor},
+};
+use bevy::{prelude::*, render::camera::Camera};
+
+/// How far outside the board the edge labels sit.
+const EDGE_OFFSET: f32 = 0.9;
+
+/// A text label pinned to a spot on the board, kept there as the camera moves.
+enum CoordinateLabel {
+    /// The file letter, shown below the board's near edge.
+    File(u8),
+    /// The rank number, shown beside the board's left edge.
+    Rank(u8),
+    /// The square's name, shown on the square itself when teaching coordinates.
+    Square(u8, u8),
+}
+
+impl CoordinateLabel {
+    fn text(&self) -> String {
+        match *self {
+            CoordinateLabel::File(file) => ((b'a' + file) as char).to_string(),
+            CoordinateLabel::Rank(rank) => (rank + 1).to_string(),
+            CoordinateLabel::Square(x, y) => square_name((x, y)),
+        }
+    }
+
+    /// Where on the board the label goes, with `bottom` being the side nearest the camera.
+    fn position(&self, bottom: PieceColor) -> Vec3 {
+        // The board's near and left edges, seen from the bottom side
+        let edge = match bottom {
+            PieceColor::White => -0.5 - EDGE_OFFSET / 2.,
+            PieceColor::Black => 7.5 + EDGE_OFFSET / 2.,
+        };
+
+        match *self {
+            CoordinateLabel::File(file) => Vec3::new(edge, 0., file as f32),
+            CoordinateLabel::Rank(rank) => Vec3::new(rank as f32, 0., edge),
+            CoordinateLabel::Square(x, y) => Vec3::new(x as f32, 0., y as f32),
+        }
+    }
+
+    fn is_visible(&self, settings: &GameSettings) -> bool {
+        match self {
+            CoordinateLabel::Square(_, _) => settings.show_square_names,
+            _ => true,
+        }
+    }
+}
+
+fn create_labels(commands: &mut Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    let mut labels = Vec::new();
+    for i in 0..8 {
+        labels.push(CoordinateLabel::File(i));
+        labels.push(CoordinateLabel::Rank(i));
+    }
+    for x in 0..8 {
+        for y in 0..8 {
+            labels.push(CoordinateLabel::Square(x, y));
+        }
+    }
+
+    for label in labels {
+        let (font_size, color) = match label {
+            CoordinateLabel::Square(_, _) => (16., Color::rgb(0.5, 0.5, 0.5)),
+            _ => (24., Color::rgb(0.8, 0.8, 0.8)),
+        };
+
+        commands
+            .spawn(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                text: Text {
+                    value: label.text(),
+                    font: font.clone(),
+                    style: TextStyle {
+                        font_size,
+                        color,
+                        ..Default::default()
+                    },
+                },
+                ..Default::default()
+            })
+            .with(label);
+    }
+}
+
+/// Projects a point on the board to window coordinates, measured from the bottom left.
+fn world_to_screen(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    window: &Window,
+    position: Vec3,
+) -> Option<Vec2> {
+    let clip = camera.projection_matrix
+        * camera_transform.compute_matrix().inverse()
+        * position.extend(1.);
+
+    // Behind the camera
+    if clip.w <= 0. {
+        return None;
+    }
+
+    Some(Vec2::new(
+        (clip.x / clip.w + 1.) / 2. * window.width(),
+        (clip.y / clip.w + 1.) / 2. * window.height(),
+    ))
+}
+
+fn place_labels(
+    windows: Res<Windows>,
+    view: Res<BoardView>,
+    settings: Res<GameSettings>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<BoardCamera>>,
+    mut query: Query<(&CoordinateLabel, &Text, &mut Style, &mut Visible)>,
+) {
+    let window = if let Some(window) = windows.get_primary() {
+        window
+    } else {
+        return;
+    };
+
+    for (camera, camera_transform) in camera_query.iter() {
+        for (label, text, mut style, mut visible) in query.iter_mut() {
+            let screen_position = world_to_screen(
+                camera,
+                camera_transform,
+                window,
+                label.position(view.bottom()),
+            );
+
+            match screen_position {
+                Some(screen_position) if label.is_visible(&settings) => {
+                    // Center the text on its spot, roughly, from its font size
+                    let half_size = Vec2::new(
+                        text.style.font_size * 0.3 * text.value.len() as f32,
+                        text.style.font_size / 2.,
+                    );
+                    style.position = Rect {
+                        left: Val::Px(screen_position.x - half_size.x),
+                        bottom: Val::Px(screen_position.y - half_size.y),
+                        ..Default::default()
+                    };
+                    visible.is_visible = true;
+                }
+                _ => visible.is_visible = false,
+            }
+        }
+    }
+}
+
+pub struct CoordinatesPlugin;
+
+impl Plugin for CoordinatesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(create_labels.system())
+            .add_system(place_labels.system());
+    }
+}
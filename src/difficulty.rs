@@ -0,0 +1,119 @@
+//! How strongly and in what style the computer plays.
+
+/// Named levels of play, from one that blunders often to the full strength of the search.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    Beginner,
+    Casual,
+    Club,
+    Expert,
+    Master,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Casual,
+        Difficulty::Club,
+        Difficulty::Expert,
+        Difficulty::Master,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Casual => "Casual",
+            Difficulty::Club => "Club",
+            Difficulty::Expert => "Expert",
+            Difficulty::Master => "Master",
+        }
+    }
+
+    /// Reads a level by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|d| d == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// What the computer aims for, whatever its strength.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlayStyle {
+    Balanced,
+    /// Brings its pieces towards the enemy king
+    Aggressive,
+    /// Keeps its pieces around its own king
+    Defensive,
+}
+
+impl PlayStyle {
+    pub const ALL: [PlayStyle; 3] = [
+        PlayStyle::Balanced,
+        PlayStyle::Aggressive,
+        PlayStyle::Defensive,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayStyle::Balanced => "Balanced",
+            PlayStyle::Aggressive => "Aggressive",
+            PlayStyle::Defensive => "Defensive",
+        }
+    }
+
+    /// Reads a style by name, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|style| style.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|s| s == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Everything that shapes how the computer picks its moves.
+#[derive(Clone, Copy)]
+pub struct Personality {
+    /// Plies searched at most
+    pub depth: u32,
+    /// Each position's score is moved by up to this many centipawns either way, at random
+    pub noise: i32,
+    /// How often, between 0 and 1, a worse move than the best one found is played
+    pub blunder_chance: f32,
+    /// Seconds to think for at most
+    pub think_time: f32,
+    /// Whether endgames the tablebases know are played perfectly from them
+    pub use_tablebases: bool,
+    pub style: PlayStyle,
+}
+
+impl Personality {
+    pub fn new(difficulty: Difficulty, style: PlayStyle) -> Self {
+        let (depth, noise, blunder_chance, think_time, use_tablebases) = match difficulty {
+            Difficulty::Beginner => (1, 150, 0.3, 0.5, false),
+            Difficulty::Casual => (2, 80, 0.15, 1., false),
+            Difficulty::Club => (3, 30, 0.05, 2., false),
+            Difficulty::Expert => (5, 10, 0., 3., true),
+            Difficulty::Master => (32, 0, 0., 5., true),
+        };
+        Self {
+            depth,
+            noise,
+            blunder_chance,
+            think_time,
+            use_tablebases,
+            style,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Hints for learners: the move the search would play for the side to move, shown on the board.
+
+use crate::{
+    ai,
+    board::{GameMode, GameSettings, MoveHistory, PlayerTurn},
+    keyboard::MoveEntry,
+    pieces::{Move, PieceColor},
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver},
+    Arc, Mutex,
+};
+
+/// How deep the search looks for a hint.
+const HINT_DEPTH: u32 = 4;
+
+/// Asks for a hint in the position being played.
+pub struct HintRequest;
+
+/// The hint given in the current position, and how many each side has asked for this game.
+#[derive(Default)]
+pub struct Hints {
+    /// The suggested move, with how many moves had been played when it was given
+    suggestion: Option<(usize, Move)>,
+    pub white: u32,
+    pub black: u32,
+}
+
+impl Hints {
+    /// The move suggested after `count` moves, if a hint was asked for there.
+    pub fn suggestion(&self, count: usize) -> Option<Move> {
+        self.suggestion
+            .filter(|(moves, _)| *moves == count)
+            .map(|(_, mv)| mv)
+    }
+
+    pub fn used(&self, color: PieceColor) -> u32 {
+        match color {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+}
+
+/// Asks for a hint with H.
+fn hint_key(
+    keyboard_input: Res<Input<KeyCode>>,
+    entry: Res<MoveEntry>,
+    mut requests: ResMut<Events<HintRequest>>,
+) {
+    // H is part of a move while one is being typed
+    if !entry.typing && keyboard_input.just_pressed(KeyCode::H) {
+        requests.send(HintRequest);
+    }
+}
+
+/// The search for a hint, running on another thread so the board keeps drawing meanwhile.
+#[derive(Default)]
+struct HintSearch {
+    /// How many moves had been played when the hint was asked for
+    count: usize,
+    reply: Option<Mutex<Receiver<Move>>>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl HintSearch {
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.reply = None;
+    }
+}
+
+/// Starts searching for the side to move when a hint is asked for. Asking again in the same
+/// position shows the same hint without searching or counting it twice.
+fn give_hint(
+    mut request_reader: Local<EventReader<HintRequest>>,
+    requests: Res<Events<HintRequest>>,
+    settings: Res<GameSettings>,
+    turn: Res<PlayerTurn>,
+    history: Res<MoveHistory>,
+    hints: Res<Hints>,
+    mut search: ResMut<HintSearch>,
+) {
+    if request_reader.iter(&requests).next().is_none() {
+        return;
+    }
+
+    // No hints for the AI's moves
+    if settings.mode == GameMode::HumanVsAi && turn.0 != settings.human_color {
+        return;
+    }
+    let count = history.0.len();
+    let searching = search.reply.is_some() && search.count == count;
+    if hints.suggestion(count).is_some() || searching {
+        return;
+    }
+
+    search.stop();
+    let position = history.bitboards();
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, reply) = channel();
+    let thread_stop = stop.clone();
+    std::thread::spawn(move || {
+        let best = ai::search(&position, HINT_DEPTH, &thread_stop)
+            .and_then(|result| result.pv.first().copied());
+        if let Some(mv) = best {
+            let _ = sender.send(mv);
+        }
+    });
+    *search = HintSearch {
+        count,
+        reply: Some(Mutex::new(reply)),
+        stop: Some(stop),
+    };
+}
+
+/// Shows the hint once the search finds it, counting it against the side to move. A hint that
+/// comes back after a move has been played is no use any more, so it's dropped.
+fn receive_hint(
+    turn: Res<PlayerTurn>,
+    history: Res<MoveHistory>,
+    mut search: ResMut<HintSearch>,
+    mut hints: ResMut<Hints>,
+) {
+    if search.reply.is_some() && search.count != history.0.len() {
+        search.stop();
+    }
+    let mv = match &search.reply {
+        Some(reply) => reply.lock().unwrap().try_recv().ok(),
+        None => return,
+    };
+    if let Some(mv) = mv {
+        search.stop();
+        hints.suggestion = Some((search.count, mv));
+        match turn.0 {
+            PieceColor::White => hints.white += 1,
+            PieceColor::Black => hints.black += 1,
+        }
+    }
+}
+
+fn reset_hints(mut hints: ResMut<Hints>) {
+    *hints = Hints::default();
+}
+
+fn stop_hint_search(mut search: ResMut<HintSearch>) {
+    search.stop();
+}
+
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Hints>()
+            .init_resource::<HintSearch>()
+            .add_event::<HintRequest>()
+            .on_state_enter(APP_STATE_STAGE, AppState::Playing, reset_hints.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, hint_key.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, give_hint.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, receive_hint.system())
+            .on_state_exit(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                stop_hint_search.system(),
+            );
+    }
+}
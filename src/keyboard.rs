@@ -0,0 +1,175 @@
+use crate::{
+    board::{GameMode, GameSettings, MoveEvent, PlayerTurn, SelectedSquare, Square},
+    camera::BoardView,
+    pgn,
+    pieces::{Piece, PieceColor},
+    AppState, APP_STATE_STAGE,
+};
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+/// A move being typed in SAN or UCI notation, opened with Tab and played with Enter.
+#[derive(Default)]
+pub struct MoveEntry {
+    /// Keys go to the move field rather than to their usual shortcuts while this is set
+    pub typing: bool,
+    pub text: String,
+    /// Why the last move entered wasn't played
+    pub error: Option<String>,
+}
+
+/// The square walked to with the arrow keys, if they've been used.
+#[derive(Default)]
+pub struct KeyboardCursor {
+    pub square: Option<(u8, u8)>,
+}
+
+impl KeyboardCursor {
+    /// Steps along the board as seen from `bottom`, so up always moves away from the player.
+    fn step(&mut self, bottom: PieceColor, up: i8, right: i8) {
+        let (home_rank, forward) = match bottom {
+            PieceColor::White => (0, 1),
+            PieceColor::Black => (7, -1),
+        };
+
+        // The first press only shows the cursor, on the bottom side's king's file
+        let (rank, file) = match self.square {
+            Some(square) => square,
+            None => {
+                self.square = Some((home_rank, 4));
+                return;
+            }
+        };
+
+        let rank = (rank as i8 + up * forward).clamp(0, 7);
+        let file = (file as i8 + right * forward).clamp(0, 7);
+        self.square = Some((rank as u8, file as u8));
+    }
+}
+
+/// Keeps typed characters that can appear in a move, e.g. `Nxf3`, `e2e4` or `O-O`.
+fn is_move_character(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "-=+#".contains(c)
+}
+
+/// Plays the move typed in, or explains why it can't be played.
+fn submit_move(
+    entry: &mut MoveEntry,
+    turn: PieceColor,
+    settings: &GameSettings,
+    pieces: &[Piece],
+    move_events: &mut Events<MoveEvent>,
+) {
+    let text = std::mem::take(&mut entry.text);
+    if text.is_empty() {
+        entry.typing = false;
+        return;
+    }
+
+    if settings.mode == GameMode::HumanVsAi && turn != settings.human_color {
+        entry.error = Some("Wait for your turn".to_string());
+        return;
+    }
+
+    match pgn::parse_move(&text, turn, pieces) {
+        Some(mv) => {
+            move_events.send(MoveEvent(mv));
+            entry.typing = false;
+            entry.error = None;
+        }
+        None => entry.error = Some(format!("{} is not a legal move", text)),
+    }
+}
+
+/// Types moves after Tab, or walks the cursor with the arrow keys and selects its square with
+/// Enter, just like clicking it.
+///
+/// Both are handled here so an Enter that plays a typed move doesn't also select a square.
+fn keyboard_moves(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut char_reader: Local<EventReader<ReceivedCharacter>>,
+    char_events: Res<Events<ReceivedCharacter>>,
+    turn: Res<PlayerTurn>,
+    settings: Res<GameSettings>,
+    view: Res<BoardView>,
+    mut entry: ResMut<MoveEntry>,
+    mut cursor: ResMut<KeyboardCursor>,
+    mut selected_square: ResMut<SelectedSquare>,
+    mut move_events: ResMut<Events<MoveEvent>>,
+    squares_query: Query<(Entity, &Square)>,
+    pieces_query: Query<&Piece>,
+) {
+    // Always read the characters, so ones typed before opening the field don't end up in it
+    let typed = char_reader
+        .iter(&char_events)
+        .map(|event| event.char)
+        .collect::<String>();
+
+    if entry.typing {
+        if keyboard_input.just_pressed(KeyCode::Escape) {
+            entry.typing = false;
+            entry.text.clear();
+            entry.error = None;
+        } else if keyboard_input.just_pressed(KeyCode::Back) {
+            entry.text.pop();
+        } else if keyboard_input.just_pressed(KeyCode::Return) {
+            let pieces = pieces_query.iter().copied().collect::<Vec<Piece>>();
+            submit_move(&mut entry, turn.0, &settings, &pieces, &mut move_events);
+        } else {
+            entry
+                .text
+                .extend(typed.chars().filter(|c| is_move_character(*c)));
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        entry.typing = true;
+        entry.error = None;
+        return;
+    }
+
+    let bottom = view.bottom();
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        cursor.step(bottom, 1, 0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        cursor.step(bottom, -1, 0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        cursor.step(bottom, 0, 1);
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        cursor.step(bottom, 0, -1);
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        cursor.square = None;
+    }
+
+    let chosen = cursor
+        .square
+        .filter(|_| keyboard_input.just_pressed(KeyCode::Return));
+    if let Some(position) = chosen {
+        if let Some((entity, _)) = squares_query
+            .iter()
+            .find(|(_, square)| (square.x, square.y) == position)
+        {
+            selected_square.entity = Some(entity);
+        }
+    }
+}
+
+fn reset_keyboard(mut entry: ResMut<MoveEntry>, mut cursor: ResMut<KeyboardCursor>) {
+    *entry = MoveEntry::default();
+    cursor.square = None;
+}
+
+pub struct KeyboardPlugin;
+
+impl Plugin for KeyboardPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<MoveEntry>()
+            .init_resource::<KeyboardCursor>()
+            .on_state_update(APP_STATE_STAGE, AppState::Playing, keyboard_moves.system())
+            .on_state_exit(APP_STATE_STAGE, AppState::Playing, reset_keyboard.system());
+    }
+}
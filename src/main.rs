@@ -1,24 +1,69 @@
+// Bevy systems take their resources and queries as arguments, so long signatures are expected
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 use bevy::prelude::*;
-use bevy_mod_picking::{DebugPickingPlugin, PickSource, PickingPlugin};
+use bevy_mod_picking::{DebugPickingPlugin, PickingPlugin};
 
+mod ai;
+mod analysis;
+mod animation;
+mod annotations;
+mod announcer;
+mod bitboard;
 mod board;
+mod book;
+mod camera;
+mod coordinates;
+mod difficulty;
+mod hint;
+mod keyboard;
+mod openings;
+mod pgn;
+mod piece_set;
 mod pieces;
+mod replay;
+mod review;
+mod tablebase;
+mod theme;
+mod tournament;
+mod uci;
 mod ui;
-use board::BoardPlugin;
+mod zobrist;
+use ai::AiPlugin;
+use analysis::AnalysisPlugin;
+use animation::AnimationPlugin;
+use annotations::AnnotationsPlugin;
+use announcer::AnnouncerPlugin;
+use board::{BoardPlugin, GameSettings};
+use book::BookPlugin;
+use camera::CameraPlugin;
+use coordinates::CoordinatesPlugin;
+use difficulty::{Difficulty, PlayStyle};
+use hint::HintPlugin;
+use keyboard::KeyboardPlugin;
+use openings::OpeningsPlugin;
+use piece_set::PieceSetPlugin;
+use pieces::PieceColor;
 use pieces::PiecesPlugin;
+use replay::ReplayPlugin;
+use review::ReviewPlugin;
+use tablebase::TablebasePlugin;
+use theme::ThemePlugin;
 use ui::UiPlugin;
 
+pub const APP_STATE_STAGE: &str = "app_state";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppState {
+    MainMenu,
+    Playing,
+    GameOver,
+    Replay,
+    Settings,
+}
+
 fn setup(commands: &mut Commands) {
     commands
-        // Camera
-        .spawn(Camera3dBundle {
-            transform: Transform::from_matrix(Mat4::from_rotation_translation(
-                Quat::from_xyzw(-0.3, -0.5, -0.3, 0.5).normalize(),
-                Vec3::new(-7.0, 20.0, 4.0),
-            )),
-            ..Default::default()
-        })
-        .with(PickSource::default())
         // Light
         .spawn(LightBundle {
             transform: Transform::from_translation(Vec3::new(4.0, 8.0, 4.0)),
@@ -27,6 +72,56 @@ fn setup(commands: &mut Commands) {
 }
 
 fn main() {
+    // `perft <depth>` counts the move sequences from the starting position, to check and time
+    // move generation, instead of opening the game
+    let args = std::env::args().collect::<Vec<_>>();
+    if let [_, command, depth] = args.as_slice() {
+        if command == "perft" {
+            let depth = depth.parse().expect("perft depth should be a number");
+            let position =
+                bitboard::Position::from_pieces(&pieces::starting_position(), PieceColor::White);
+            let start = std::time::Instant::now();
+            let nodes = position.perft(depth);
+            println!("perft({}) = {} in {:?}", depth, nodes, start.elapsed());
+            return;
+        }
+    }
+
+    // `tournament <first> <second> ...` plays engines against each other without a window
+    if args.get(1).map(String::as_str) == Some("tournament") {
+        if let Err(err) = tournament::run(&args[2..]) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // `--book <path>` and `--tablebases <path>` read the opening book and tablebases from
+    // elsewhere than the defaults, `--engine <path>` analyses with a UCI engine, and
+    // `--difficulty <level>` and `--style <style>` set how the computer plays
+    let mut settings = GameSettings::default();
+    let mut options = args.iter().skip(1);
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => value.clone(),
+            None => break,
+        };
+        match option.as_str() {
+            "--book" => settings.book_path = value,
+            "--tablebases" => settings.tablebase_path = value,
+            "--engine" => settings.engine_path = Some(value),
+            "--difficulty" => match Difficulty::parse(&value) {
+                Some(difficulty) => settings.difficulty = difficulty,
+                None => eprintln!("Unknown difficulty {}", value),
+            },
+            "--style" => match PlayStyle::parse(&value) {
+                Some(style) => settings.style = style,
+                None => eprintln!("Unknown style {}", value),
+            },
+            _ => eprintln!("Unknown option {}", option),
+        }
+    }
+
     App::build()
         .add_resource(Msaa { samples: 4 })
         .add_resource(WindowDescriptor {
@@ -36,10 +131,34 @@ fn main() {
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
+        .add_resource(State::new(AppState::MainMenu))
+        .add_resource(settings)
+        .add_stage_after(
+            stage::UPDATE,
+            APP_STATE_STAGE,
+            StateStage::<AppState>::default(),
+        )
         .add_plugin(PickingPlugin)
         .add_plugin(DebugPickingPlugin)
+        // Loaded first, as the board and pieces are created from them
+        .add_plugin(ThemePlugin)
+        .add_plugin(PieceSetPlugin)
         .add_plugin(BoardPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(CoordinatesPlugin)
         .add_plugin(PiecesPlugin)
+        .add_plugin(AnimationPlugin)
+        .add_plugin(AnnotationsPlugin)
+        .add_plugin(KeyboardPlugin)
+        .add_plugin(AnnouncerPlugin)
+        .add_plugin(BookPlugin)
+        .add_plugin(OpeningsPlugin)
+        .add_plugin(TablebasePlugin)
+        .add_plugin(AiPlugin)
+        .add_plugin(HintPlugin)
+        .add_plugin(AnalysisPlugin)
+        .add_plugin(ReplayPlugin)
+        .add_plugin(ReviewPlugin)
         .add_plugin(UiPlugin)
         .add_startup_system(setup.system())
         .run();
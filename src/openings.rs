@@ -0,0 +1,79 @@
+use crate::{
+    bitboard::Position,
+    board::MoveHistory,
+    pgn,
+    pieces::{starting_position, PieceColor},
+};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Where opening names are read from: a tab-separated table of ECO code, name and the moves
+/// that reach the opening, under an `eco name pgn` header.
+const OPENINGS_PATH: &str = "assets/openings/eco.tsv";
+
+#[derive(Clone)]
+pub struct Opening {
+    /// The opening's code in the Encyclopaedia of Chess Openings, e.g. `B90`
+    pub eco: String,
+    pub name: String,
+}
+
+/// Every named opening, by the Zobrist key of the position its moves reach, so games that
+/// transpose into an opening are named too.
+#[derive(Default)]
+pub struct Openings(HashMap<u64, Opening>);
+
+impl Openings {
+    fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+        let mut openings = HashMap::new();
+        for (number, line) in contents.lines().enumerate().skip(1) {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let (eco, name, movetext) = match fields.as_slice() {
+                [eco, name, movetext] => (eco, name, movetext),
+                _ => return Err(format!("line {} should have 3 fields", number + 1)),
+            };
+            let moves = pgn::parse_moves(movetext)
+                .map_err(|err| format!("line {}: {}", number + 1, err))?;
+
+            let mut position = Position::from_pieces(&starting_position(), PieceColor::White);
+            for mv in moves {
+                position.play(mv);
+            }
+            openings.insert(
+                position.key,
+                Opening {
+                    eco: eco.to_string(),
+                    name: name.to_string(),
+                },
+            );
+        }
+
+        Ok(Self(openings))
+    }
+
+    /// The opening after the first `count` moves, named after the last of their positions that
+    /// has a name, so it stays named once the game leaves the table.
+    pub fn after(&self, history: &MoveHistory, count: usize) -> Option<&Opening> {
+        let mut position = Position::from_pieces(&starting_position(), PieceColor::White);
+        let mut opening = None;
+        for mv in history.0.iter().take(count) {
+            position.play(*mv);
+            opening = self.0.get(&position.key).or(opening);
+        }
+        opening
+    }
+}
+
+pub struct OpeningsPlugin;
+
+impl Plugin for OpeningsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let openings = Openings::load(OPENINGS_PATH).unwrap_or_else(|err| {
+            eprintln!("Could not load openings from {}: {}", OPENINGS_PATH, err);
+            Openings::default()
+        });
+        app.add_resource(openings);
+    }
+}
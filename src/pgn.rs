@@ -0,0 +1,276 @@
+use crate::{
+    annotations::Annotations,
+    board::{GameResult, GameSettings, MoveHistory},
+    hint::Hints,
+    openings::Openings,
+    pieces::{
+        apply_move, is_in_check, legal_moves, parse_square, square_name, starting_position, Move,
+        Piece, PieceColor, PieceType,
+    },
+    review::GameReview,
+};
+use std::{io, path::Path};
+
+fn piece_letter(piece_type: PieceType) -> Option<char> {
+    match piece_type {
+        PieceType::King => Some('K'),
+        PieceType::Queen => Some('Q'),
+        PieceType::Rook => Some('R'),
+        PieceType::Bishop => Some('B'),
+        PieceType::Knight => Some('N'),
+        PieceType::Pawn => None,
+    }
+}
+
+/// Returns the move in standard algebraic notation, e.g. `Nbd7+`, given the pieces before it is played.
+pub fn san(mv: Move, pieces: &[Piece]) -> String {
+    let piece = if let Some(piece) = pieces.iter().find(|piece| (piece.x, piece.y) == mv.from) {
+        *piece
+    } else {
+        return mv.notation();
+    };
+    let is_capture = pieces.iter().any(|other| (other.x, other.y) == mv.to);
+    let file = (b'a' + mv.from.1) as char;
+    let rank = (b'1' + mv.from.0) as char;
+
+    let mut san = String::new();
+    if let Some(letter) = piece_letter(piece.piece_type) {
+        san.push(letter);
+
+        // Disambiguate from other pieces of the same type that can reach the same square
+        let rivals = legal_moves(piece.color, pieces)
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && pieces.iter().any(|rival| {
+                        (rival.x, rival.y) == other.from && rival.piece_type == piece.piece_type
+                    })
+            })
+            .collect::<Vec<Move>>();
+        if !rivals.is_empty() {
+            if rivals.iter().all(|other| other.from.1 != mv.from.1) {
+                san.push(file);
+            } else if rivals.iter().all(|other| other.from.0 != mv.from.0) {
+                san.push(rank);
+            } else {
+                san.push(file);
+                san.push(rank);
+            }
+        }
+    } else if is_capture {
+        san.push(file);
+    }
+
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&square_name(mv.to));
+
+    let mut pieces_after = pieces.to_vec();
+    apply_move(&mut pieces_after, mv);
+    let opponent = piece.color.opposite();
+    if is_in_check(opponent, &pieces_after) {
+        san.push(if legal_moves(opponent, &pieces_after).is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+
+    san
+}
+
+fn strip_annotations(san: &str) -> &str {
+    san.trim_end_matches(|c: char| "+#!?".contains(c))
+}
+
+/// Renders a whole game, tag pairs first and then the movetext, with any arrows and marked
+/// squares as comments after the moves they were drawn on, along with the review's marks.
+pub fn game_to_pgn(
+    history: &MoveHistory,
+    result: &GameResult,
+    settings: &GameSettings,
+    annotations: &Annotations,
+    openings: &Openings,
+    review: &GameReview,
+    hints: &Hints,
+) -> String {
+    let mut headers = vec![
+        ("Event", "Casual Game".to_string()),
+        ("Site", "bevy-chess".to_string()),
+        ("Date", "????.??.??".to_string()),
+        ("Round", "-".to_string()),
+        ("White", settings.player_name(PieceColor::White).to_string()),
+        ("Black", settings.player_name(PieceColor::Black).to_string()),
+        ("Result", result.pgn_result().to_string()),
+        ("Termination", result.description()),
+    ];
+    if let Some(opening) = openings.after(history, history.0.len()) {
+        headers.push(("ECO", opening.eco.clone()));
+        headers.push(("Opening", opening.name.clone()));
+    }
+    if hints.white + hints.black > 0 {
+        headers.push(("WhiteHints", hints.white.to_string()));
+        headers.push(("BlackHints", hints.black.to_string()));
+    }
+
+    let mut pgn = String::new();
+    push_headers(&mut pgn, &headers);
+
+    let mut pieces = starting_position();
+    let mut tokens = Vec::new();
+    if let Some(commands) = annotations.pgn_commands(0) {
+        tokens.push(format!("{{{}}}", commands));
+    }
+    for (ply, mv) in history.0.iter().enumerate() {
+        if ply % 2 == 0 {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        }
+        tokens.push(san(*mv, &pieces));
+        if let Some(nag) = review.at(ply).and_then(|review| review.quality.nag()) {
+            tokens.push(format!("${}", nag));
+        }
+        let comment = review.comment(ply, &pieces);
+        apply_move(&mut pieces, *mv);
+
+        let comment = match (comment, annotations.pgn_commands(ply + 1)) {
+            (Some(comment), Some(commands)) => Some(format!("{} {}", comment, commands)),
+            (comment, commands) => comment.or(commands),
+        };
+        if let Some(comment) = comment {
+            tokens.push(format!("{{{}}}", comment));
+            // Black's move needs its number repeated after a comment
+            if ply % 2 == 0 && ply + 1 < history.0.len() {
+                tokens.push(format!("{}...", ply / 2 + 1));
+            }
+        }
+    }
+    tokens.push(result.pgn_result().to_string());
+    push_movetext(&mut pgn, tokens);
+
+    pgn
+}
+
+/// Writes the tag pairs, then the blank line that ends them.
+pub fn push_headers(pgn: &mut String, headers: &[(&str, String)]) {
+    for (name, value) in headers.iter() {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "'")));
+    }
+    pgn.push('\n');
+}
+
+/// Writes the movetext tokens, e.g. move numbers, moves and comments, with spaces between them.
+pub fn push_movetext(pgn: &mut String, tokens: Vec<String>) {
+    // Keep lines under 80 characters, as the export format asks for
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > 79 {
+            pgn.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            pgn.push(' ');
+            line_length += 1;
+        }
+        line_length += token.len();
+        pgn.push_str(&token);
+    }
+    pgn.push('\n');
+}
+
+pub fn save(
+    path: &str,
+    history: &MoveHistory,
+    result: &GameResult,
+    settings: &GameSettings,
+    annotations: &Annotations,
+    openings: &Openings,
+    review: &GameReview,
+    hints: &Hints,
+) -> io::Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(
+        path,
+        game_to_pgn(
+            history,
+            result,
+            settings,
+            annotations,
+            openings,
+            review,
+            hints,
+        ),
+    )
+}
+
+/// Finds the legal move written in SAN, e.g. `Nf3`, ignoring check marks and annotations.
+fn parse_san(text: &str, color: PieceColor, pieces: &[Piece]) -> Option<Move> {
+    let wanted = strip_annotations(text);
+    legal_moves(color, pieces)
+        .into_iter()
+        .find(|mv| strip_annotations(&san(*mv, pieces)) == wanted)
+}
+
+/// Finds the legal move written in either SAN, e.g. `Nf3`, or UCI notation, e.g. `g1f3`.
+pub fn parse_move(text: &str, color: PieceColor, pieces: &[Piece]) -> Option<Move> {
+    let text = text.trim();
+    let from = text.get(..2).and_then(parse_square);
+    let to = text.get(2..).and_then(parse_square);
+    if let (Some(from), Some(to)) = (from, to) {
+        return Some(Move { from, to }).filter(|mv| legal_moves(color, pieces).contains(mv));
+    }
+
+    parse_san(text, color, pieces)
+}
+
+/// Reads the moves of the first game in a PGN file, ignoring comments and variations.
+pub fn parse_moves(text: &str) -> Result<Vec<Move>, String> {
+    let mut movetext = String::new();
+    let mut in_comment = false;
+    let mut variation_depth = 0;
+    for line in text.lines() {
+        if !in_comment && line.trim_start().starts_with('[') {
+            continue;
+        }
+
+        for c in line.chars() {
+            match c {
+                '{' => in_comment = true,
+                '}' => in_comment = false,
+                '(' if !in_comment => variation_depth += 1,
+                ')' if !in_comment => variation_depth -= 1,
+                ';' if !in_comment && variation_depth == 0 => break,
+                _ if in_comment || variation_depth > 0 => {}
+                _ => movetext.push(c),
+            }
+        }
+        movetext.push(' ');
+    }
+
+    let mut pieces = starting_position();
+    let mut color = PieceColor::White;
+    let mut moves = Vec::new();
+    for token in movetext.split_whitespace() {
+        if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token) {
+            break;
+        }
+
+        // Skip move numbers and numeric annotation glyphs
+        let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if token.is_empty() || token.starts_with('$') {
+            continue;
+        }
+
+        let mv =
+            parse_san(token, color, &pieces).ok_or_else(|| format!("illegal move `{}`", token))?;
+
+        apply_move(&mut pieces, mv);
+        color = color.opposite();
+        moves.push(mv);
+    }
+
+    Ok(moves)
+}
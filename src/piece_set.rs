@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+/// Where piece set manifests are read from, one `.ron` file per set.
+const PIECE_SETS_DIR: &str = "assets/piece_sets";
+
+/// How one kind of piece is drawn.
+#[derive(Clone, Deserialize)]
+pub struct PieceModel {
+    /// Asset paths of the glTF meshes making up the piece, e.g. `models/pieces.glb#Mesh0/Primitive0`
+    pub meshes: Vec<String>,
+    /// Where the meshes sit relative to the center of the piece's square
+    pub offset: (f32, f32, f32),
+    pub scale: f32,
+    /// Degrees turned around the vertical axis
+    pub rotation: f32,
+}
+
+impl PieceModel {
+    pub fn transform(&self) -> Transform {
+        let (x, y, z) = self.offset;
+        Transform {
+            translation: Vec3::new(x, y, z),
+            rotation: Quat::from_rotation_y(self.rotation * PI / 180.),
+            scale: Vec3::new(self.scale, self.scale, self.scale),
+        }
+    }
+}
+
+/// A manifest describing the models of a set of pieces.
+#[derive(Clone, Deserialize)]
+pub struct PieceSet {
+    pub name: String,
+    pub king: PieceModel,
+    pub queen: PieceModel,
+    pub rook: PieceModel,
+    pub bishop: PieceModel,
+    pub knight: PieceModel,
+    pub pawn: PieceModel,
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        let model = |meshes: &[u8], offset| PieceModel {
+            meshes: meshes
+                .iter()
+                .map(|mesh| format!("models/pieces.glb#Mesh{}/Primitive0", mesh))
+                .collect(),
+            offset,
+            scale: 0.2,
+            rotation: 0.,
+        };
+
+        Self {
+            name: "Standard".to_string(),
+            king: model(&[0, 1], (-0.2, 0., -1.9)),
+            queen: model(&[7], (-0.2, 0., -0.95)),
+            rook: model(&[5], (-0.1, 0., 1.8)),
+            bishop: model(&[6], (-0.1, 0., 0.)),
+            knight: model(&[3, 4], (-0.2, 0., 0.9)),
+            pawn: model(&[2], (-0.2, 0., 2.6)),
+        }
+    }
+}
+
+/// Every piece set that can be switched to, in the order they're cycled through.
+pub struct PieceSets(pub Vec<PieceSet>);
+
+impl PieceSets {
+    /// The set after the one with the given name, wrapping around.
+    pub fn next_after(&self, name: &str) -> PieceSet {
+        let index = self
+            .0
+            .iter()
+            .position(|set| set.name == name)
+            .map_or(0, |index| index + 1);
+
+        self.0[index % self.0.len()].clone()
+    }
+}
+
+fn load_piece_set(path: &std::path::Path) -> Result<PieceSet, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    ron::de::from_str(&contents).map_err(|err| err.to_string())
+}
+
+/// Reads every manifest in `PIECE_SETS_DIR`, sorted by file name, falling back to the built-in set.
+fn load_piece_sets() -> Vec<PieceSet> {
+    let mut paths = match std::fs::read_dir(PIECE_SETS_DIR) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension() == Some("ron".as_ref()))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            eprintln!("Could not read piece sets from {}: {}", PIECE_SETS_DIR, err);
+            Vec::new()
+        }
+    };
+    paths.sort();
+
+    let mut sets = Vec::new();
+    for path in paths {
+        match load_piece_set(&path) {
+            Ok(set) => sets.push(set),
+            Err(err) => eprintln!("Could not load piece set {}: {}", path.display(), err),
+        }
+    }
+
+    if sets.is_empty() {
+        sets.push(PieceSet::default());
+    }
+    sets
+}
+
+pub struct PieceSetPlugin;
+
+impl Plugin for PieceSetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let sets = load_piece_sets();
+
+        app.add_resource(sets[0].clone())
+            .add_resource(PieceSets(sets));
+    }
+}
@@ -1,4 +1,17 @@
+use crate::{
+    bitboard::Position,
+    board::Taken,
+    camera::{BoardCamera, BoardView, RenderMode},
+    piece_set::{PieceModel, PieceSet},
+    theme::Theme,
+    AppState, APP_STATE_STAGE,
+};
 use bevy::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+
+/// How far sprites float above the board, under annotations but over the squares.
+const SPRITE_HEIGHT: f32 = 0.005;
+const SPRITE_SIZE: f32 = 0.9;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum PieceColor {
@@ -6,6 +19,15 @@ pub enum PieceColor {
     White,
 }
 
+impl PieceColor {
+    pub fn opposite(&self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum PieceType {
     Bishop,
@@ -24,550 +46,445 @@ pub struct Piece {
     pub y: u8,
 }
 
-impl Piece {
-    pub fn is_move_valid(&self, new_position: (u8, u8), pieces: &[Piece]) -> bool {
-        // If there's a piece of the same color in the new position, we can't move there.
-        if color_of_piece_on_square(new_position, pieces) == Some(self.color) {
-            return false;
-        }
+/// A move of the piece standing on `from` to `to`, in board coordinates.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Move {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+}
 
-        // We can't move to the same place we currently are
-        if (self.x, self.y) == new_position {
-            return false;
-        }
+impl Move {
+    pub fn notation(&self) -> String {
+        format!("{}{}", square_name(self.from), square_name(self.to))
+    }
+}
 
-        let x_diff = (self.x as i8 - new_position.0 as i8).abs();
-        let y_diff = (self.y as i8 - new_position.1 as i8).abs();
+/// Returns the algebraic name of a square, e.g. `e4`.
+pub fn square_name(position: (u8, u8)) -> String {
+    format!("{}{}", (b'a' + position.1) as char, position.0 + 1)
+}
 
-        match self.piece_type {
-            PieceType::King => x_diff <= 1 && y_diff <= 1,
-            PieceType::Queen => {
-                is_path_empty((self.x, self.y), new_position, &pieces)
-                    && (x_diff == y_diff || x_diff == 0 || y_diff == 0)
-            }
-            PieceType::Rook => {
-                (x_diff == 0 || y_diff == 0)
-                    && is_path_empty((self.x, self.y), new_position, &pieces)
-            }
-            PieceType::Bishop => {
-                x_diff == y_diff && is_path_empty((self.x, self.y), new_position, &pieces)
-            }
-            PieceType::Knight => (x_diff == 2 && y_diff == 1) || (x_diff == 1 && y_diff == 2),
-            PieceType::Pawn => match self.color {
-                PieceColor::White => {
-                    if new_position.0 as i8 - self.x as i8 == 1 {
-                        // Normal move
-                        if y_diff == 0 {
-                            return true;
-                        }
-                        // Taking piece
-                        if y_diff == 1
-                            && color_of_piece_on_square(new_position, &pieces)
-                                == Some(PieceColor::Black)
-                        {
-                            return true;
-                        }
-                    }
-                    // Move two squares ahead
-                    if self.x == 1
-                        && y_diff == 0
-                        && x_diff == 2
-                        && is_path_empty((self.x, self.y), new_position, &pieces)
-                    {
-                        return true;
-                    }
-                    false
-                }
-                PieceColor::Black => {
-                    if self.x as i8 - new_position.0 as i8 == 1 {
-                        // Normal move
-                        if y_diff == 0 {
-                            return true;
-                        }
-                        // Taking piece
-                        if y_diff == 1
-                            && color_of_piece_on_square(new_position, &pieces)
-                                == Some(PieceColor::White)
-                        {
-                            return true;
-                        }
-                    }
-                    // Move two squares ahead
-                    if self.x == 6
-                        && y_diff == 0
-                        && x_diff == 2
-                        && is_path_empty((self.x, self.y), new_position, &pieces)
-                    {
-                        return true;
-                    }
-                    false
-                }
-            },
-        }
+/// Reads an algebraic square name, e.g. `e4`, the reverse of `square_name`.
+pub fn parse_square(name: &str) -> Option<(u8, u8)> {
+    match name.as_bytes() {
+        &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1', file - b'a')),
+        _ => None,
     }
 }
 
-fn piece_transform_from_translation(translation: Vec3) -> Transform {
-    let mut transform = Transform::from_translation(translation);
-    transform.apply_non_uniform_scale(Vec3::new(0.2, 0.2, 0.2));
-    transform
+/// Returns every move for the pieces of the given color that doesn't leave its King in check.
+pub fn legal_moves(color: PieceColor, pieces: &[Piece]) -> Vec<Move> {
+    Position::from_pieces(pieces, color).legal_moves()
 }
 
-fn spawn_king(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    mesh_cross: Handle<Mesh>,
-    position: (u8, u8),
-) {
-    commands
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(
-                position.0 as f32,
-                0.,
-                position.1 as f32,
-            )),
-            ..Default::default()
-        })
-        .with(Piece {
-            color: piece_color,
-            piece_type: PieceType::King,
-            x: position.0,
-            y: position.1,
-        })
-        .with_children(|parent| {
-            let transform = piece_transform_from_translation(Vec3::new(-0.2, 0., -1.9));
-            parent.spawn(PbrBundle {
-                mesh,
-                material: material.clone(),
-                transform: transform.clone(),
-                ..Default::default()
-            });
-            parent.spawn(PbrBundle {
-                mesh: mesh_cross,
-                material,
-                transform,
-                ..Default::default()
-            });
-        });
+/// Returns true if the King of the given color is attacked by any opposing piece.
+pub fn is_in_check(color: PieceColor, pieces: &[Piece]) -> bool {
+    Position::from_pieces(pieces, color).is_in_check()
 }
 
-fn spawn_knight(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh_1: Handle<Mesh>,
-    mesh_2: Handle<Mesh>,
-    position: (u8, u8),
-) {
-    commands
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(
-                position.0 as f32,
-                0.,
-                position.1 as f32,
-            )),
-            ..Default::default()
-        })
-        .with(Piece {
-            color: piece_color,
-            piece_type: PieceType::Knight,
-            x: position.0,
-            y: position.1,
-        })
-        .with_children(|parent| {
-            let transform = piece_transform_from_translation(Vec3::new(-0.2, 0., 0.9));
-            parent.spawn(PbrBundle {
-                mesh: mesh_1,
-                material: material.clone(),
-                transform: transform.clone(),
-                ..Default::default()
+/// Returns true if neither side has enough material left to checkmate.
+pub fn is_insufficient_material(pieces: &[Piece]) -> bool {
+    let others = pieces
+        .iter()
+        .filter(|piece| piece.piece_type != PieceType::King)
+        .collect::<Vec<&Piece>>();
+
+    match others.as_slice() {
+        [] => true,
+        [piece] => piece.piece_type == PieceType::Bishop || piece.piece_type == PieceType::Knight,
+        // Bishops that all travel on the same square color can never mate
+        [first, ..] => others.iter().all(|piece| {
+            piece.piece_type == PieceType::Bishop
+                && (piece.x + piece.y) % 2 == (first.x + first.y) % 2
+        }),
+    }
+}
+
+/// Plays a move on a list of pieces, removing any piece it captures.
+pub fn apply_move(pieces: &mut Vec<Piece>, mv: Move) {
+    pieces.retain(|piece| (piece.x, piece.y) != mv.to);
+    if let Some(piece) = pieces
+        .iter_mut()
+        .find(|piece| (piece.x, piece.y) == mv.from)
+    {
+        piece.x = mv.to.0;
+        piece.y = mv.to.1;
+    }
+}
+
+/// The pieces in their starting positions.
+pub fn starting_position() -> Vec<Piece> {
+    let back_rank = [
+        PieceType::Rook,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Queen,
+        PieceType::King,
+        PieceType::Bishop,
+        PieceType::Knight,
+        PieceType::Rook,
+    ];
+
+    let mut pieces = Vec::new();
+    for &(color, back_x, pawn_x) in [(PieceColor::White, 0, 1), (PieceColor::Black, 7, 6)].iter() {
+        for (y, piece_type) in back_rank.iter().enumerate() {
+            pieces.push(Piece {
+                color,
+                piece_type: *piece_type,
+                x: back_x,
+                y: y as u8,
             });
-            parent.spawn(PbrBundle {
-                mesh: mesh_2,
-                material,
-                transform,
-                ..Default::default()
+            pieces.push(Piece {
+                color,
+                piece_type: PieceType::Pawn,
+                x: pawn_x,
+                y: y as u8,
             });
-        });
+        }
+    }
+
+    pieces
 }
 
-fn spawn_queen(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    position: (u8, u8),
-) {
-    commands
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(
-                position.0 as f32,
-                0.,
-                position.1 as f32,
-            )),
-            ..Default::default()
-        })
-        .with(Piece {
-            color: piece_color,
-            piece_type: PieceType::Queen,
-            x: position.0,
-            y: position.1,
-        })
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh,
-                material: material,
-                transform: piece_transform_from_translation(Vec3::new(-0.2, 0., -0.95)),
-                ..Default::default()
-            });
-        });
+/// Reads the pieces and the side to move from a position in Forsyth-Edwards Notation. The game
+/// has no castling or en passant, and doesn't keep the move counters, so the rest is ignored.
+pub fn parse_fen(fen: &str) -> Result<(Vec<Piece>, PieceColor), String> {
+    let mut fields = fen.split_whitespace();
+    let placement = fields.next().ok_or("the FEN is empty")?;
+    let side_to_move = match fields.next() {
+        Some("w") | None => PieceColor::White,
+        Some("b") => PieceColor::Black,
+        Some(side) => return Err(format!("{} is not a side to move", side)),
+    };
+
+    let ranks = placement.split('/').collect::<Vec<_>>();
+    if ranks.len() != 8 {
+        return Err(format!("{} should have 8 ranks", placement));
+    }
+
+    let mut pieces = Vec::new();
+    for (row, rank) in ranks.iter().enumerate() {
+        let x = 7 - row as u8;
+        let mut y = 0;
+        for c in rank.chars() {
+            if let Some(empty) = c.to_digit(10) {
+                y += empty as u8;
+                continue;
+            }
+            let piece_type = match c.to_ascii_lowercase() {
+                'p' => PieceType::Pawn,
+                'n' => PieceType::Knight,
+                'b' => PieceType::Bishop,
+                'r' => PieceType::Rook,
+                'q' => PieceType::Queen,
+                'k' => PieceType::King,
+                _ => return Err(format!("{} is not a piece", c)),
+            };
+            let color = if c.is_ascii_uppercase() {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            };
+            if y < 8 {
+                pieces.push(Piece {
+                    color,
+                    piece_type,
+                    x,
+                    y,
+                });
+            }
+            y += 1;
+        }
+        if y != 8 {
+            return Err(format!("{} should have 8 squares", rank));
+        }
+    }
+
+    Ok((pieces, side_to_move))
 }
 
-fn spawn_bishop(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    position: (u8, u8),
-) {
-    commands
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(
-                position.0 as f32,
-                0.,
-                position.1 as f32,
-            )),
-            ..Default::default()
-        })
-        .with(Piece {
-            color: piece_color,
-            piece_type: PieceType::Bishop,
-            x: position.0,
-            y: position.1,
-        })
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh,
-                material: material,
-                transform: piece_transform_from_translation(Vec3::new(-0.1, 0., 0.)),
-                ..Default::default()
-            });
-        });
+/// The loaded meshes of one kind of piece, and where they sit on the square.
+pub struct PieceModelHandles {
+    meshes: Vec<Handle<Mesh>>,
+    transform: Transform,
 }
 
-fn spawn_rook(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    position: (u8, u8),
-) {
-    commands
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(
-                position.0 as f32,
-                0.,
-                position.1 as f32,
-            )),
-            ..Default::default()
-        })
-        .with(Piece {
-            color: piece_color,
-            piece_type: PieceType::Rook,
-            x: position.0,
-            y: position.1,
-        })
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh,
-                material: material,
-                transform: piece_transform_from_translation(Vec3::new(-0.1, 0., 1.8)),
-                ..Default::default()
-            });
-        });
+impl PieceModelHandles {
+    fn load(model: &PieceModel, asset_server: &AssetServer) -> Self {
+        PieceModelHandles {
+            meshes: model
+                .meshes
+                .iter()
+                .map(|path| asset_server.load(path.as_str()))
+                .collect(),
+            transform: model.transform(),
+        }
+    }
 }
 
-fn spawn_pawn(
-    commands: &mut Commands,
-    material: Handle<StandardMaterial>,
-    piece_color: PieceColor,
-    mesh: Handle<Mesh>,
-    position: (u8, u8),
-) {
-    commands
-        .spawn(PbrBundle {
-            transform: Transform::from_translation(Vec3::new(
-                position.0 as f32,
-                0.,
-                position.1 as f32,
-            )),
-            ..Default::default()
-        })
-        .with(Piece {
-            color: piece_color,
-            piece_type: PieceType::Pawn,
-            x: position.0,
-            y: position.1,
-        })
-        .with_children(|parent| {
-            parent.spawn(PbrBundle {
-                mesh,
-                material: material,
-                transform: piece_transform_from_translation(Vec3::new(-0.2, 0., 2.6)),
-                ..Default::default()
-            });
-        });
+/// Flat images of one side's pieces, drawn in the 2D view.
+pub struct PieceSprites {
+    king: Handle<StandardMaterial>,
+    queen: Handle<StandardMaterial>,
+    rook: Handle<StandardMaterial>,
+    bishop: Handle<StandardMaterial>,
+    knight: Handle<StandardMaterial>,
+    pawn: Handle<StandardMaterial>,
 }
 
-fn create_pieces(
-    commands: &mut Commands,
-    asset_server: Res<AssetServer>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    // Load meshes
-    let king_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh0/Primitive0");
-    let king_cross_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh1/Primitive0");
-    let pawn_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh2/Primitive0");
-    let knight_1_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh3/Primitive0");
-    let knight_2_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh4/Primitive0");
-    let rook_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh5/Primitive0");
-    let bishop_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh6/Primitive0");
-    let queen_handle: Handle<Mesh> = asset_server.load("models/pieces.glb#Mesh7/Primitive0");
-
-    // Create materials
-    let white_material = materials.add(Color::rgb(1., 0.8, 0.8).into());
-    let black_material = materials.add(Color::rgb(0., 0.2, 0.2).into());
-
-    // White Pieces
-    spawn_rook(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        rook_handle.clone(),
-        (0, 0),
-    );
-    spawn_knight(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (0, 1),
-    );
-    spawn_bishop(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        bishop_handle.clone(),
-        (0, 2),
-    );
-    spawn_queen(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        queen_handle.clone(),
-        (0, 3),
-    );
-    spawn_king(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        king_handle.clone(),
-        king_cross_handle.clone(),
-        (0, 4),
-    );
-    spawn_bishop(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        bishop_handle.clone(),
-        (0, 5),
-    );
-    spawn_knight(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (0, 6),
-    );
-    spawn_rook(
-        commands,
-        white_material.clone(),
-        PieceColor::White,
-        rook_handle.clone(),
-        (0, 7),
-    );
-
-    for i in 0..8 {
-        spawn_pawn(
-            commands,
-            white_material.clone(),
-            PieceColor::White,
-            pawn_handle.clone(),
-            (1, i),
-        );
+impl PieceSprites {
+    /// Loads `sprites/<color>_<piece>.png` for each kind of piece.
+    fn load(
+        color: &str,
+        asset_server: &AssetServer,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Self {
+        let mut sprite = |name: &str| {
+            materials.add(StandardMaterial {
+                albedo_texture: Some(
+                    asset_server.load(format!("sprites/{}_{}.png", color, name).as_str()),
+                ),
+                shaded: false,
+                ..Default::default()
+            })
+        };
+
+        PieceSprites {
+            king: sprite("king"),
+            queen: sprite("queen"),
+            rook: sprite("rook"),
+            bishop: sprite("bishop"),
+            knight: sprite("knight"),
+            pawn: sprite("pawn"),
+        }
     }
 
-    // Black Pieces
-    spawn_rook(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        rook_handle.clone(),
-        (7, 0),
-    );
-    spawn_knight(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (7, 1),
-    );
-    spawn_bishop(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        bishop_handle.clone(),
-        (7, 2),
-    );
-    spawn_queen(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        queen_handle.clone(),
-        (7, 3),
-    );
-    spawn_king(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        king_handle.clone(),
-        king_cross_handle.clone(),
-        (7, 4),
-    );
-    spawn_bishop(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        bishop_handle.clone(),
-        (7, 5),
-    );
-    spawn_knight(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        knight_1_handle.clone(),
-        knight_2_handle.clone(),
-        (7, 6),
-    );
-    spawn_rook(
-        commands,
-        black_material.clone(),
-        PieceColor::Black,
-        rook_handle.clone(),
-        (7, 7),
-    );
-
-    for i in 0..8 {
-        spawn_pawn(
-            commands,
-            black_material.clone(),
-            PieceColor::Black,
-            pawn_handle.clone(),
-            (6, i),
-        );
+    fn get(&self, piece_type: PieceType) -> Handle<StandardMaterial> {
+        match piece_type {
+            PieceType::King => self.king.clone(),
+            PieceType::Queen => self.queen.clone(),
+            PieceType::Rook => self.rook.clone(),
+            PieceType::Bishop => self.bishop.clone(),
+            PieceType::Knight => self.knight.clone(),
+            PieceType::Pawn => self.pawn.clone(),
+        }
     }
 }
 
-fn move_pieces(time: Res<Time>, mut query: Query<(&mut Transform, &Piece)>) {
-    for (mut transform, piece) in query.iter_mut() {
-        let direction = Vec3::new(piece.x as f32, 0., piece.y as f32) - transform.translation;
+/// Marks the sprite drawn in place of a piece's meshes in the 2D view.
+struct PieceSprite;
+
+pub struct PieceMeshes {
+    king: PieceModelHandles,
+    queen: PieceModelHandles,
+    rook: PieceModelHandles,
+    bishop: PieceModelHandles,
+    knight: PieceModelHandles,
+    pawn: PieceModelHandles,
+    pub white_material: Handle<StandardMaterial>,
+    pub black_material: Handle<StandardMaterial>,
+    sprite_mesh: Handle<Mesh>,
+    white_sprites: PieceSprites,
+    black_sprites: PieceSprites,
+}
+
+impl PieceMeshes {
+    pub fn model(&self, piece_type: PieceType) -> &PieceModelHandles {
+        match piece_type {
+            PieceType::King => &self.king,
+            PieceType::Queen => &self.queen,
+            PieceType::Rook => &self.rook,
+            PieceType::Bishop => &self.bishop,
+            PieceType::Knight => &self.knight,
+            PieceType::Pawn => &self.pawn,
+        }
+    }
+
+    pub fn material(&self, color: PieceColor) -> Handle<StandardMaterial> {
+        match color {
+            PieceColor::White => self.white_material.clone(),
+            PieceColor::Black => self.black_material.clone(),
+        }
+    }
 
-        // Only move the piece if it isn't there already (distance is sufficiently large)
-        if direction.length() > 0.1 {
-            transform.translation += direction.normalize() * time.delta_seconds();
+    fn sprite(&self, piece: &Piece) -> Handle<StandardMaterial> {
+        match piece.color {
+            PieceColor::White => self.white_sprites.get(piece.piece_type),
+            PieceColor::Black => self.black_sprites.get(piece.piece_type),
         }
     }
+
+    /// Loads the meshes of the given piece set, keeping the materials.
+    fn load_set(&mut self, set: &PieceSet, asset_server: &AssetServer) {
+        self.king = PieceModelHandles::load(&set.king, asset_server);
+        self.queen = PieceModelHandles::load(&set.queen, asset_server);
+        self.rook = PieceModelHandles::load(&set.rook, asset_server);
+        self.bishop = PieceModelHandles::load(&set.bishop, asset_server);
+        self.knight = PieceModelHandles::load(&set.knight, asset_server);
+        self.pawn = PieceModelHandles::load(&set.pawn, asset_server);
+    }
 }
 
-fn get_piece_on_square(position: (u8, u8), pieces: &[Piece]) -> Option<&Piece> {
-    for piece in pieces {
-        if piece.x == position.0 && piece.y == position.1 {
-            return Some(&piece);
+impl FromResources for PieceMeshes {
+    fn from_resources(resources: &Resources) -> Self {
+        let asset_server = resources.get::<AssetServer>().unwrap();
+        let mut materials = resources.get_mut::<Assets<StandardMaterial>>().unwrap();
+        let mut meshes = resources.get_mut::<Assets<Mesh>>().unwrap();
+        let theme = resources.get::<Theme>().unwrap();
+        let set = resources.get::<PieceSet>().unwrap();
+        let mut white_material = StandardMaterial::default();
+        theme.white_pieces.apply(&mut white_material);
+        let mut black_material = StandardMaterial::default();
+        theme.black_pieces.apply(&mut black_material);
+
+        PieceMeshes {
+            // Load meshes
+            king: PieceModelHandles::load(&set.king, &asset_server),
+            queen: PieceModelHandles::load(&set.queen, &asset_server),
+            rook: PieceModelHandles::load(&set.rook, &asset_server),
+            bishop: PieceModelHandles::load(&set.bishop, &asset_server),
+            knight: PieceModelHandles::load(&set.knight, &asset_server),
+            pawn: PieceModelHandles::load(&set.pawn, &asset_server),
+
+            // Create materials
+            white_material: materials.add(white_material),
+            black_material: materials.add(black_material),
+
+            // Load sprites
+            sprite_mesh: meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
+                SPRITE_SIZE,
+                SPRITE_SIZE,
+            )))),
+            white_sprites: PieceSprites::load("white", &asset_server, &mut materials),
+            black_sprites: PieceSprites::load("black", &asset_server, &mut materials),
         }
     }
+}
+
+/// Spawns a piece on its square, drawn with the current piece set, and returns its entity.
+///
+/// Both its meshes and its sprite start hidden, until `show_render_mode` picks one of them.
+pub fn spawn_piece(commands: &mut Commands, meshes: &PieceMeshes, piece: Piece) -> Entity {
+    let model = meshes.model(piece.piece_type);
+    let material = meshes.material(piece.color);
+
+    commands
+        .spawn(PbrBundle {
+            transform: Transform::from_translation(Vec3::new(piece.x as f32, 0., piece.y as f32)),
+            ..Default::default()
+        })
+        .with(piece)
+        .with_children(|parent| {
+            for mesh in model.meshes.iter() {
+                parent.spawn(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    visible: Visible {
+                        is_visible: false,
+                        ..Default::default()
+                    },
+                    transform: model.transform,
+                    ..Default::default()
+                });
+            }
+            parent
+                .spawn(PbrBundle {
+                    mesh: meshes.sprite_mesh.clone(),
+                    material: meshes.sprite(&piece),
+                    visible: Visible {
+                        is_visible: false,
+                        is_transparent: true,
+                    },
+                    transform: Transform::from_translation(Vec3::new(0., SPRITE_HEIGHT, 0.)),
+                    ..Default::default()
+                })
+                .with(PieceSprite);
+        });
 
-    None
+    commands.current_entity().unwrap()
 }
 
-/// Returns the color of the piece at the given position, None otherwise.
-fn color_of_piece_on_square(position: (u8, u8), pieces: &[Piece]) -> Option<PieceColor> {
-    match get_piece_on_square(position, pieces) {
-        Some(piece) => Some(piece.color),
-        None => None,
+/// Spawns an entity for each of the given pieces.
+pub fn spawn_pieces(commands: &mut Commands, meshes: &PieceMeshes, pieces: &[Piece]) {
+    for piece in pieces {
+        spawn_piece(commands, meshes, *piece);
     }
 }
 
-fn is_between_range(start: u8, end: u8, value: u8) -> bool {
-    (value > start && value < end) || (value > end && value < start)
+fn create_pieces(commands: &mut Commands, meshes: Res<PieceMeshes>) {
+    spawn_pieces(commands, &meshes, &starting_position());
 }
 
-fn is_path_empty(begin: (u8, u8), end: (u8, u8), pieces: &[Piece]) -> bool {
-    // Same column
-    if begin.0 == end.0 {
-        for piece in pieces {
-            if piece.x == begin.0 && is_between_range(begin.1, end.1, piece.y) {
-                return false;
-            }
-        }
+/// Clears the board and sets up the pieces for a new game.
+fn reset_pieces(
+    commands: &mut Commands,
+    meshes: Res<PieceMeshes>,
+    query: Query<Entity, Or<(With<Piece>, With<Taken>)>>,
+) {
+    for entity in query.iter() {
+        commands.despawn_recursive(entity);
     }
 
-    // Same row
-    if begin.1 == end.1 {
-        for piece in pieces {
-            if piece.y == begin.1 && is_between_range(begin.0, end.0, piece.x) {
-                return false;
-            }
-        }
+    spawn_pieces(commands, &meshes, &starting_position());
+}
+
+/// Reloads the meshes when another piece set is picked, and redraws the pieces with them.
+fn swap_piece_set(
+    commands: &mut Commands,
+    set: ChangedRes<PieceSet>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<PieceMeshes>,
+    query: Query<(Entity, &Piece)>,
+) {
+    meshes.load_set(&set, &asset_server);
+
+    let mut pieces = Vec::new();
+    for (entity, piece) in query.iter() {
+        commands.despawn_recursive(entity);
+        pieces.push(*piece);
     }
 
-    // Diagonals
-    let x_diff = (begin.0 as i8 - end.0 as i8).abs();
-    let y_diff = (begin.1 as i8 - end.1 as i8).abs();
-    if x_diff == y_diff {
-        for i in 1..x_diff {
-            let position = if begin.0 < end.0 && begin.1 < end.1 {
-                // Left Down -> Right Up
-                (begin.0 + i as u8, begin.1 + i as u8)
-            } else if begin.0 < end.0 && begin.1 > end.1 {
-                // Left Up -> Right Down
-                (begin.0 + i as u8, begin.1 - i as u8)
-            } else if begin.0 > end.0 && begin.1 < end.1 {
-                // Right Down -> Left Up
-                (begin.0 - i as u8, begin.1 + i as u8)
-            } else {
-                // Right Up -> Left Down
-                (begin.0 - i as u8, begin.1 - i as u8)
-            };
+    spawn_pieces(commands, &meshes, &pieces);
+}
 
-            if get_piece_on_square(position, pieces).is_some() {
-                return false;
+/// Shows either the meshes or the sprite of each piece, with sprites upright on the screen.
+fn show_render_mode(
+    view: Res<BoardView>,
+    camera_query: Query<&GlobalTransform, With<BoardCamera>>,
+    pieces_query: Query<&Children, Or<(With<Piece>, With<Taken>)>>,
+    mut parts_query: Query<(&mut Visible, &mut Transform, Option<&PieceSprite>)>,
+) {
+    // The way up the screen, along the board
+    let up = match camera_query.iter().next() {
+        Some(camera_transform) => camera_transform.rotation * Vec3::unit_y(),
+        None => return,
+    };
+    let sprite_rotation =
+        Quat::from_rotation_y((-up.x).atan2(-up.z)) * Quat::from_rotation_x(-FRAC_PI_2);
+
+    for children in pieces_query.iter() {
+        for child in children.iter() {
+            if let Ok((mut visible, mut transform, sprite)) = parts_query.get_mut(*child) {
+                visible.is_visible = match view.render_mode {
+                    RenderMode::ThreeD => sprite.is_none(),
+                    RenderMode::TwoD => sprite.is_some(),
+                };
+                if sprite.is_some() {
+                    transform.rotation = sprite_rotation;
+                }
             }
         }
     }
-
-    true
 }
 
 pub struct PiecesPlugin;
 
 impl Plugin for PiecesPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(create_pieces.system())
-            .add_system(move_pieces.system());
+        app.init_resource::<PieceMeshes>()
+            .add_startup_system(create_pieces.system())
+            .on_state_enter(APP_STATE_STAGE, AppState::Playing, reset_pieces.system())
+            .add_system(swap_piece_set.system())
+            .add_system(show_render_mode.system());
     }
 }
@@ -0,0 +1,79 @@
+use crate::{
+    board::{MoveHistory, Taken, SAVE_PATH},
+    pgn,
+    pieces::{spawn_pieces, Move, Piece, PieceMeshes},
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
+
+/// How far into the loaded game the replay has stepped.
+#[derive(Default)]
+pub struct ReplayCursor(pub usize);
+
+fn load_game(path: &str) -> Result<Vec<Move>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    pgn::parse_moves(&contents)
+}
+
+fn start_replay(
+    mut state: ResMut<State<AppState>>,
+    mut history: ResMut<MoveHistory>,
+    mut cursor: ResMut<ReplayCursor>,
+) {
+    match load_game(SAVE_PATH) {
+        Ok(moves) => {
+            history.0 = moves;
+            cursor.0 = 0;
+        }
+        Err(err) => {
+            eprintln!("Could not load game from {}: {}", SAVE_PATH, err);
+            state.set_next(AppState::MainMenu).unwrap();
+        }
+    }
+}
+
+fn step_replay(
+    keyboard_input: Res<Input<KeyCode>>,
+    history: Res<MoveHistory>,
+    mut cursor: ResMut<ReplayCursor>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Right) && cursor.0 < history.0.len() {
+        cursor.0 += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) && cursor.0 > 0 {
+        cursor.0 -= 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        state.set_next(AppState::MainMenu).unwrap();
+    }
+}
+
+fn show_replay_position(
+    commands: &mut Commands,
+    cursor: ChangedRes<ReplayCursor>,
+    history: Res<MoveHistory>,
+    meshes: Res<PieceMeshes>,
+    query: Query<Entity, Or<(With<Piece>, With<Taken>)>>,
+) {
+    for entity in query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    spawn_pieces(commands, &meshes, &history.position_after(cursor.0));
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ReplayCursor>()
+            .on_state_enter(APP_STATE_STAGE, AppState::Replay, start_replay.system())
+            .on_state_update(APP_STATE_STAGE, AppState::Replay, step_replay.system())
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Replay,
+                show_replay_position.system(),
+            );
+    }
+}
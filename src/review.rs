@@ -0,0 +1,261 @@
+//! Review of a finished game, rating each move by how much worse it left the position than the
+//! best move would have, as searched on a background thread.
+
+use crate::{
+    ai,
+    bitboard::Position,
+    board::MoveHistory,
+    pgn,
+    pieces::{apply_move, starting_position, Move, Piece, PieceColor},
+    AppState, APP_STATE_STAGE,
+};
+use bevy::prelude::*;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+
+/// How deep each position of the game is searched.
+const REVIEW_DEPTH: u32 = 4;
+
+/// Scores are capped at this, in centipawns, so missing a mate is a blunder without making
+/// every other loss look small beside it.
+const SCORE_CAP: i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MoveQuality {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveQuality {
+    /// Rates a move other than the best one by the centipawns it gave away.
+    fn from_loss(loss: i32) -> Self {
+        match loss {
+            loss if loss >= 300 => MoveQuality::Blunder,
+            loss if loss >= 100 => MoveQuality::Mistake,
+            loss if loss >= 50 => MoveQuality::Inaccuracy,
+            _ => MoveQuality::Good,
+        }
+    }
+
+    /// The move's mark, e.g. `??` for a blunder.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            MoveQuality::Best | MoveQuality::Good => "",
+            MoveQuality::Inaccuracy => "?!",
+            MoveQuality::Mistake => "?",
+            MoveQuality::Blunder => "??",
+        }
+    }
+
+    /// The numeric annotation glyph PGN writes the mark as, e.g. `$4` for `??`.
+    pub fn nag(&self) -> Option<u8> {
+        match self {
+            MoveQuality::Best | MoveQuality::Good => None,
+            MoveQuality::Inaccuracy => Some(6),
+            MoveQuality::Mistake => Some(2),
+            MoveQuality::Blunder => Some(4),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MoveQuality::Best => "Best move",
+            MoveQuality::Good => "Good move",
+            MoveQuality::Inaccuracy => "Inaccuracy",
+            MoveQuality::Mistake => "Mistake",
+            MoveQuality::Blunder => "Blunder",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MoveReview {
+    pub quality: MoveQuality,
+    /// Centipawns given away compared to the best move
+    pub loss: i32,
+    /// The move the search preferred
+    pub best: Option<Move>,
+}
+
+/// The review of the last finished game, filled in move by move as it's worked out.
+#[derive(Default)]
+pub struct GameReview {
+    /// Reviews of the moves so far, in the order they were played
+    pub moves: Vec<MoveReview>,
+    reviews: Option<Mutex<Receiver<MoveReview>>>,
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl GameReview {
+    /// The review of the move at `ply`, counting from 0, once it's been worked out.
+    pub fn at(&self, ply: usize) -> Option<&MoveReview> {
+        self.moves.get(ply)
+    }
+
+    /// What to say about a move in a PGN comment, e.g. "Mistake (-1.20), Nf3 was best", given
+    /// the pieces before it.
+    pub fn comment(&self, ply: usize, pieces: &[Piece]) -> Option<String> {
+        // Only marked moves are worth a comment
+        let review = self
+            .at(ply)
+            .filter(|review| review.quality.nag().is_some())?;
+        let verdict = format!(
+            "{} (-{:.2})",
+            review.quality.name(),
+            review.loss as f32 / 100.
+        );
+        Some(match review.best {
+            Some(best) => format!("{}, {} was best", verdict, pgn::san(best, pieces)),
+            None => verdict,
+        })
+    }
+
+    fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.reviews = None;
+    }
+}
+
+/// Searches every position of the game in turn, sending each move's review once the positions
+/// before and after it are both searched.
+fn review_moves(moves: Vec<Move>, stop: Arc<AtomicBool>, reviews: Sender<MoveReview>) {
+    let mut position = Position::from_pieces(&starting_position(), PieceColor::White);
+    let mut before = match ai::search(&position, REVIEW_DEPTH, &stop) {
+        Some(result) => result,
+        None => return,
+    };
+
+    for mv in moves {
+        position.play(mv);
+        let after = match ai::search(&position, REVIEW_DEPTH, &stop) {
+            Some(result) => result,
+            None => return,
+        };
+
+        // Both scores from the point of view of the side that moved
+        let best_score = before.score.clamp(-SCORE_CAP, SCORE_CAP);
+        let played_score = (-after.score).clamp(-SCORE_CAP, SCORE_CAP);
+        let loss = (best_score - played_score).max(0);
+        let best = before.pv.first().copied();
+        let quality = if best == Some(mv) {
+            MoveQuality::Best
+        } else {
+            MoveQuality::from_loss(loss)
+        };
+
+        if reviews
+            .send(MoveReview {
+                quality,
+                loss,
+                best,
+            })
+            .is_err()
+        {
+            return;
+        }
+        before = after;
+    }
+}
+
+/// Writes out the moves with their marks, e.g. "1. e4 e5 2. Qh5?! Nc6 3. Bc4 Nf6??", a few moves
+/// to a line, followed by how many of each mark each side got.
+pub fn move_list(history: &MoveHistory, review: &GameReview) -> String {
+    let mut pieces = starting_position();
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for (ply, mv) in history.0.iter().enumerate() {
+        if ply % 2 == 0 {
+            if ply > 0 && ply % 10 == 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push_str(&format!("{}. ", ply / 2 + 1));
+        }
+        line.push_str(&pgn::san(*mv, &pieces));
+        if let Some(review) = review.at(ply) {
+            line.push_str(review.quality.symbol());
+        }
+        line.push(' ');
+        apply_move(&mut pieces, *mv);
+    }
+    lines.push(line);
+
+    if review.moves.len() < history.0.len() {
+        lines.push(format!(
+            "Reviewing move {} of {}...",
+            review.moves.len() + 1,
+            history.0.len()
+        ));
+    } else {
+        for (color, first_ply) in [("White", 0), ("Black", 1)].iter() {
+            let count = |quality| {
+                review
+                    .moves
+                    .iter()
+                    .skip(*first_ply)
+                    .step_by(2)
+                    .filter(|review| review.quality == quality)
+                    .count()
+            };
+            lines.push(format!(
+                "{}: {} best, {} inaccuracies, {} mistakes, {} blunders",
+                color,
+                count(MoveQuality::Best),
+                count(MoveQuality::Inaccuracy),
+                count(MoveQuality::Mistake),
+                count(MoveQuality::Blunder)
+            ));
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn start_review(history: Res<MoveHistory>, mut review: ResMut<GameReview>) {
+    review.stop();
+    review.moves.clear();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = channel();
+    let moves = history.0.clone();
+    let thread_stop = stop.clone();
+    std::thread::spawn(move || review_moves(moves, thread_stop, sender));
+
+    review.reviews = Some(Mutex::new(receiver));
+    review.stop = Some(stop);
+}
+
+fn receive_reviews(mut review: ResMut<GameReview>) {
+    let received = match &review.reviews {
+        Some(reviews) => reviews.lock().unwrap().try_iter().collect::<Vec<_>>(),
+        None => return,
+    };
+    review.moves.extend(received);
+}
+
+/// Stops reviewing once the game is left, keeping the moves reviewed so far.
+fn stop_review(mut review: ResMut<GameReview>) {
+    review.stop();
+}
+
+pub struct ReviewPlugin;
+
+impl Plugin for ReviewPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<GameReview>()
+            .on_state_enter(APP_STATE_STAGE, AppState::GameOver, start_review.system())
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::GameOver,
+                receive_reviews.system(),
+            )
+            .on_state_exit(APP_STATE_STAGE, AppState::GameOver, stop_review.system());
+    }
+}
@@ -0,0 +1,173 @@
+//! Syzygy endgame tablebases, which know the result of every position with few enough pieces
+//! and the quickest way to it.
+
+use crate::{
+    bitboard::Position,
+    board::GameSettings,
+    pieces::{parse_square, Move, PieceColor, PieceType},
+};
+use bevy::prelude::*;
+use shakmaty::{fen::Fen, CastlingMode, Chess};
+use shakmaty_syzygy::{Syzygy, Tablebase, Wdl};
+
+/// Where tablebase files are read from, unless another directory is given with
+/// `--tablebases <path>`.
+pub const DEFAULT_TABLEBASE_PATH: &str = "tablebases";
+
+/// What the tablebases know about a position, for the side to move.
+#[derive(Clone, Copy)]
+pub struct Probe {
+    pub wdl: Wdl,
+    /// Plies until the next capture on the quickest way to the result, negative when losing
+    pub dtz: i32,
+}
+
+impl Probe {
+    /// The result with best play, e.g. "White wins, DTZ 12".
+    pub fn description(&self, side_to_move: PieceColor) -> String {
+        let winner = match self.wdl {
+            Wdl::Win => side_to_move,
+            Wdl::Loss => side_to_move.opposite(),
+            // Wins that take too long are drawn by the fifty-move rule
+            Wdl::CursedWin | Wdl::BlessedLoss | Wdl::Draw => return "Draw".to_string(),
+        };
+        let winner = match winner {
+            PieceColor::White => "White",
+            PieceColor::Black => "Black",
+        };
+        format!("{} wins, DTZ {}", winner, self.dtz.abs())
+    }
+}
+
+/// The tablebases in `GameSettings::tablebase_path`, empty if none could be read.
+pub struct Tablebases {
+    /// The directory the tables were read from
+    path: String,
+    tables: Tablebase<Chess>,
+}
+
+impl Default for Tablebases {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            tables: Tablebase::new(),
+        }
+    }
+}
+
+impl Tablebases {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut tables = Tablebase::new();
+        tables.add_directory(path).map_err(|err| err.to_string())?;
+        Ok(Self {
+            path: path.to_string(),
+            tables,
+        })
+    }
+
+    /// The position for probing, if the tables hold it under the game's rules. Positions with
+    /// pawns are left out, as the tables count on promotion, which the game doesn't have.
+    fn covered(&self, position: &Position) -> Option<Chess> {
+        let pawns = position.of(PieceColor::White, PieceType::Pawn)
+            | position.of(PieceColor::Black, PieceType::Pawn);
+        if pawns != 0 || position.piece_count() as usize > Chess::MAX_PIECES {
+            return None;
+        }
+
+        let fen = position.fen().parse::<Fen>().ok()?;
+        fen.position(CastlingMode::Standard).ok()
+    }
+
+    pub fn probe(&self, position: &Position) -> Option<Probe> {
+        let chess = self.covered(position)?;
+        let wdl = self.tables.probe_wdl(&chess).ok()?;
+        let dtz = self.tables.probe_dtz(&chess).ok()?;
+        Some(Probe { wdl, dtz: dtz.0 })
+    }
+
+    /// The move that keeps the best result and gets there quickest.
+    pub fn best_move(&self, position: &Position) -> Option<Move> {
+        let chess = self.covered(position)?;
+        let (mv, _) = self.tables.best_move(&chess).ok()??;
+        Some(Move {
+            from: parse_square(&mv.from()?.to_string())?,
+            to: parse_square(&mv.to().to_string())?,
+        })
+    }
+}
+
+/// Reads the tables again whenever their path is changed, e.g. on startup.
+fn load_tablebases(settings: Res<GameSettings>, mut tablebases: ResMut<Tablebases>) {
+    if tablebases.path == settings.tablebase_path {
+        return;
+    }
+
+    *tablebases = Tablebases::load(&settings.tablebase_path).unwrap_or_else(|err| {
+        eprintln!(
+            "Could not load tablebases from {}: {}",
+            settings.tablebase_path, err
+        );
+        Tablebases {
+            path: settings.tablebase_path.clone(),
+            ..Default::default()
+        }
+    });
+}
+
+pub struct TablebasePlugin;
+
+impl Plugin for TablebasePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Tablebases>()
+            .add_system(load_tablebases.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::parse_fen;
+
+    fn tables() -> Tablebases {
+        Tablebases::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tables")).unwrap()
+    }
+
+    fn position(fen: &str) -> Position {
+        let (pieces, side_to_move) = parse_fen(fen).unwrap();
+        Position::from_pieces(&pieces, side_to_move)
+    }
+
+    #[test]
+    fn queen_mates_in_one() {
+        let tables = tables();
+        let mut position = position("k7/7Q/1K6/8/8/8/8/8 w - - 0 1");
+
+        let probe = tables.probe(&position).unwrap();
+        assert_eq!(probe.wdl, Wdl::Win);
+        assert_eq!(probe.description(PieceColor::White), "White wins, DTZ 1");
+
+        position.play(tables.best_move(&position).unwrap());
+        assert!(position.is_in_check());
+        assert!(position.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn hanging_rook_is_a_draw() {
+        let tables = tables();
+        let position = position("7K/8/8/8/8/8/1R6/k7 b - - 0 1");
+
+        let probe = tables.probe(&position).unwrap();
+        assert_eq!(probe.wdl, Wdl::Draw);
+        assert_eq!(probe.description(PieceColor::Black), "Draw");
+        assert_eq!(
+            tables.best_move(&position).map(|mv| mv.notation()),
+            Some("a1b2".to_string())
+        );
+    }
+
+    #[test]
+    fn positions_with_pawns_are_not_probed() {
+        let position = position("k7/8/1K6/8/8/8/P7/8 w - - 0 1");
+        assert!(tables().probe(&position).is_none());
+    }
+}
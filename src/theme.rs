@@ -0,0 +1,156 @@
+use crate::pieces::PieceMeshes;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Where themes are read from, one `.ron` file per theme.
+const THEMES_DIR: &str = "assets/themes";
+
+/// A color as `(red, green, blue)`, each between 0 and 1.
+#[derive(Clone, Copy, Deserialize)]
+pub struct Rgb(pub f32, pub f32, pub f32);
+
+impl Rgb {
+    /// Blends towards `other`, by `amount` between 0 and 1.
+    pub fn mix(self, other: Rgb, amount: f32) -> Rgb {
+        Rgb(
+            self.0 + (other.0 - self.0) * amount,
+            self.1 + (other.1 - self.1) * amount,
+            self.2 + (other.2 - self.2) * amount,
+        )
+    }
+}
+
+impl From<Rgb> for Color {
+    fn from(rgb: Rgb) -> Self {
+        Color::rgb(rgb.0, rgb.1, rgb.2)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct PieceMaterial {
+    pub color: Rgb,
+    /// Lit by the scene's light, or drawn in flat color when false
+    pub shaded: bool,
+}
+
+impl PieceMaterial {
+    pub fn apply(&self, material: &mut StandardMaterial) {
+        material.albedo = self.color.into();
+        material.shaded = self.shaded;
+    }
+}
+
+/// Colors of the board and materials of the pieces.
+#[derive(Clone, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub light_square: Rgb,
+    pub dark_square: Rgb,
+    pub selected_square: Rgb,
+    pub hovered_square: Rgb,
+    /// Blended into the squares the last move was played from and to
+    pub last_move_square: Rgb,
+    /// The square of a king in check
+    pub check_square: Rgb,
+    /// The squares of the move a hint suggests
+    pub hint_square: Rgb,
+    pub white_pieces: PieceMaterial,
+    pub black_pieces: PieceMaterial,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: "Classic".to_string(),
+            light_square: Rgb(1., 0.9, 0.9),
+            dark_square: Rgb(0., 0.1, 0.1),
+            selected_square: Rgb(0.8, 0.6, 0.1),
+            hovered_square: Rgb(0.8, 0.3, 0.3),
+            last_move_square: Rgb(0.6, 0.8, 0.2),
+            check_square: Rgb(0.9, 0.1, 0.1),
+            hint_square: Rgb(0.2, 0.5, 0.9),
+            white_pieces: PieceMaterial {
+                color: Rgb(1., 0.8, 0.8),
+                shaded: true,
+            },
+            black_pieces: PieceMaterial {
+                color: Rgb(0., 0.2, 0.2),
+                shaded: true,
+            },
+        }
+    }
+}
+
+/// Every theme that can be switched to, in the order they're cycled through.
+pub struct Themes(pub Vec<Theme>);
+
+impl Themes {
+    /// The theme after the one with the given name, wrapping around.
+    pub fn next_after(&self, name: &str) -> Theme {
+        let index = self
+            .0
+            .iter()
+            .position(|theme| theme.name == name)
+            .map_or(0, |index| index + 1);
+
+        self.0[index % self.0.len()].clone()
+    }
+}
+
+fn load_theme(path: &std::path::Path) -> Result<Theme, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    ron::de::from_str(&contents).map_err(|err| err.to_string())
+}
+
+/// Reads every theme in `THEMES_DIR`, sorted by file name, falling back to the built-in one.
+fn load_themes() -> Vec<Theme> {
+    let mut paths = match std::fs::read_dir(THEMES_DIR) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension() == Some("ron".as_ref()))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            eprintln!("Could not read themes from {}: {}", THEMES_DIR, err);
+            Vec::new()
+        }
+    };
+    paths.sort();
+
+    let mut themes = Vec::new();
+    for path in paths {
+        match load_theme(&path) {
+            Ok(theme) => themes.push(theme),
+            Err(err) => eprintln!("Could not load theme {}: {}", path.display(), err),
+        }
+    }
+
+    if themes.is_empty() {
+        themes.push(Theme::default());
+    }
+    themes
+}
+
+fn apply_piece_theme(
+    theme: ChangedRes<Theme>,
+    meshes: Res<PieceMeshes>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if let Some(material) = materials.get_mut(&meshes.white_material) {
+        theme.white_pieces.apply(material);
+    }
+    if let Some(material) = materials.get_mut(&meshes.black_material) {
+        theme.black_pieces.apply(material);
+    }
+}
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let themes = load_themes();
+
+        app.add_resource(themes[0].clone())
+            .add_resource(Themes(themes))
+            .add_system(apply_piece_theme.system());
+    }
+}
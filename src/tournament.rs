@@ -0,0 +1,396 @@
+//! Matches between two engines, played without opening a window, to compare difficulty levels,
+//! styles or UCI engines against each other.
+
+use crate::{
+    ai,
+    bitboard::Position,
+    board::{termination, GameResult, Termination},
+    difficulty::{Difficulty, Personality, PlayStyle},
+    pgn,
+    pieces::{apply_move, parse_fen, starting_position, Move, Piece, PieceColor},
+    uci::UciEngine,
+};
+use std::{path::Path, sync::atomic::AtomicBool, time::Duration};
+
+const DEFAULT_GAMES: u32 = 10;
+const DEFAULT_PGN_PATH: &str = "saves/tournament.pgn";
+const DEFAULT_MOVE_TIME: u64 = 1000;
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+
+enum Engine {
+    BuiltIn(Personality),
+    Uci(UciEngine),
+}
+
+struct Player {
+    name: String,
+    engine: Engine,
+}
+
+impl Player {
+    /// Reads a player from the command line: a difficulty level, optionally with a style after a
+    /// slash, e.g. `expert/aggressive`, or else the path to a UCI engine.
+    fn parse(spec: &str, move_time: Duration) -> Result<Self, String> {
+        let mut parts = spec.splitn(2, '/');
+        let difficulty = parts.next().and_then(Difficulty::parse);
+        let style = match parts.next() {
+            Some(style) => PlayStyle::parse(style),
+            None => Some(PlayStyle::Balanced),
+        };
+        if let (Some(difficulty), Some(style)) = (difficulty, style) {
+            let mut personality = Personality::new(difficulty, style);
+            personality.think_time = move_time.as_secs_f32();
+            return Ok(Self {
+                name: format!("{} ({})", difficulty.name(), style.name()),
+                engine: Engine::BuiltIn(personality),
+            });
+        }
+
+        let engine = UciEngine::start(spec)
+            .map_err(|err| format!("Could not start engine {}: {}", spec, err))?;
+        let name = Path::new(spec)
+            .file_stem()
+            .map_or(spec.to_string(), |stem| stem.to_string_lossy().to_string());
+        Ok(Self {
+            name,
+            engine: Engine::Uci(engine),
+        })
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        match &mut self.engine {
+            Engine::BuiltIn(_) => Ok(()),
+            Engine::Uci(engine) => engine.new_game().map_err(|err| err.to_string()),
+        }
+    }
+
+    fn choose(&mut self, position: &Position, move_time: Duration) -> Result<Move, String> {
+        let mv = match &mut self.engine {
+            Engine::BuiltIn(personality) => {
+                ai::think(position, personality, &AtomicBool::new(false))
+            }
+            Engine::Uci(engine) => engine
+                .best_move(position, move_time)
+                .map_err(|err| err.to_string())?,
+        };
+        match mv {
+            Some(mv) if position.legal_moves().contains(&mv) => Ok(mv),
+            Some(mv) => Err(format!("played the illegal move {}", mv.notation())),
+            None => Err("gave no move".to_string()),
+        }
+    }
+}
+
+/// The tournament's results from the first player's point of view.
+#[derive(Default)]
+struct Score {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+/// The Elo difference that makes `score`, between 0 and 1, the expected score.
+fn elo_difference(score: f64) -> f64 {
+    -400. * (1. / score - 1.).log10()
+}
+
+impl Score {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points scored as a share of the games played.
+    fn share(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.) / self.games() as f64
+    }
+
+    /// The Elo difference the score points to, and the margin of error either side of it with
+    /// 95% confidence, or None while one player has every point.
+    fn elo(&self) -> Option<(f64, f64)> {
+        let games = self.games() as f64;
+        let share = self.share();
+        if self.games() == 0 || share <= 0. || share >= 1. {
+            return None;
+        }
+
+        let variance = (self.wins as f64 * (1. - share).powi(2)
+            + self.draws as f64 * (0.5 - share).powi(2)
+            + self.losses as f64 * share.powi(2))
+            / games;
+        let error = 1.96 * (variance / games).sqrt();
+        let (low, high) = (share - error, share + error);
+        let margin = if low > 0. && high < 1. {
+            (elo_difference(high) - elo_difference(low)) / 2.
+        } else {
+            f64::INFINITY
+        };
+        Some((elo_difference(share), margin))
+    }
+}
+
+struct Game {
+    start: Vec<Piece>,
+    first_to_move: PieceColor,
+    moves: Vec<Move>,
+    result: GameResult,
+}
+
+/// Plays a game from `fen` to the end, forfeiting it for a player whose engine breaks down or
+/// plays an illegal move.
+fn play_game(
+    white: &mut Player,
+    black: &mut Player,
+    fen: &str,
+    move_time: Duration,
+) -> Result<Game, String> {
+    let (start, first_to_move) = parse_fen(fen).map_err(|err| format!("{}: {}", fen, err))?;
+    let mut position = Position::from_pieces(&start, first_to_move);
+    let mut moves = Vec::new();
+
+    let mut forfeit = match (white.new_game(), black.new_game()) {
+        (Err(err), _) => Some((PieceColor::White, err)),
+        (_, Err(err)) => Some((PieceColor::Black, err)),
+        _ => None,
+    };
+
+    let (winner, ending) = loop {
+        if let Some((color, err)) = &forfeit {
+            let name = match color {
+                PieceColor::White => &white.name,
+                PieceColor::Black => &black.name,
+            };
+            eprintln!("{} forfeits: {}", name, err);
+            break (Some(color.opposite()), Termination::Forfeit);
+        }
+        if let Some(ending) = termination(&start, first_to_move, &moves) {
+            break ending;
+        }
+
+        let color = position.side_to_move;
+        let player = match color {
+            PieceColor::White => &mut *white,
+            PieceColor::Black => &mut *black,
+        };
+        match player.choose(&position, move_time) {
+            Ok(mv) => {
+                position.play(mv);
+                moves.push(mv);
+            }
+            Err(err) => forfeit = Some((color, err)),
+        }
+    };
+
+    Ok(Game {
+        start,
+        first_to_move,
+        moves,
+        result: GameResult {
+            winner,
+            termination: Some(ending),
+        },
+    })
+}
+
+fn game_to_pgn(game: &Game, round: u32, white: &str, black: &str, fen: &str) -> String {
+    let mut headers = vec![
+        ("Event", "Engine Tournament".to_string()),
+        ("Site", "bevy-chess".to_string()),
+        ("Date", "????.??.??".to_string()),
+        ("Round", round.to_string()),
+        ("White", white.to_string()),
+        ("Black", black.to_string()),
+        ("Result", game.result.pgn_result().to_string()),
+        ("Termination", game.result.description()),
+    ];
+    let start = Position::from_pieces(&game.start, game.first_to_move);
+    let standard = Position::from_pieces(&starting_position(), PieceColor::White);
+    if start.key != standard.key {
+        headers.push(("SetUp", "1".to_string()));
+        headers.push(("FEN", fen.to_string()));
+    }
+
+    let mut pgn = String::new();
+    pgn::push_headers(&mut pgn, &headers);
+
+    let mut pieces = game.start.clone();
+    let mut side = game.first_to_move;
+    let mut number = 1;
+    let mut tokens = Vec::new();
+    if side == PieceColor::Black {
+        tokens.push(format!("{}...", number));
+    }
+    for mv in game.moves.iter() {
+        if side == PieceColor::White {
+            tokens.push(format!("{}.", number));
+        } else {
+            number += 1;
+        }
+        tokens.push(pgn::san(*mv, &pieces));
+        apply_move(&mut pieces, *mv);
+        side = side.opposite();
+    }
+    tokens.push(game.result.pgn_result().to_string());
+    pgn::push_movetext(&mut pgn, tokens);
+
+    pgn
+}
+
+/// Reads one FEN per line, skipping blank lines and `#` comments.
+fn load_openings(path: &str) -> Result<Vec<String>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let openings = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if openings.is_empty() {
+        return Err(format!("{} has no positions", path));
+    }
+    Ok(openings)
+}
+
+/// Runs `tournament <first> <second> [--games <n>] [--openings <file>] [--pgn <path>]
+/// [--movetime <ms>]`. Each opening is played twice, once with each player as White, and every
+/// game is written to the PGN file as it finishes.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut games = DEFAULT_GAMES;
+    let mut openings = vec![START_FEN.to_string()];
+    let mut pgn_path = DEFAULT_PGN_PATH.to_string();
+    let mut move_time = Duration::from_millis(DEFAULT_MOVE_TIME);
+    let mut specs = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            specs.push(arg.as_str());
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", arg))?;
+        match arg.as_str() {
+            "--games" => games = value.parse().map_err(|_| "--games should be a number")?,
+            "--openings" => openings = load_openings(value)?,
+            "--pgn" => pgn_path = value.clone(),
+            "--movetime" => {
+                let millis = value.parse().map_err(|_| "--movetime should be a number")?;
+                move_time = Duration::from_millis(millis);
+            }
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    let (first, second) = match specs.as_slice() {
+        [first, second] => (first, second),
+        _ => return Err("tournament needs two players".to_string()),
+    };
+    if games == 0 {
+        return Err("--games should be at least 1".to_string());
+    }
+    let mut players = [
+        Player::parse(first, move_time)?,
+        Player::parse(second, move_time)?,
+    ];
+
+    if let Some(parent) = Path::new(&pgn_path).parent() {
+        std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+
+    let mut score = Score::default();
+    let mut pgn = String::new();
+    for game in 0..games {
+        let fen = &openings[(game / 2) as usize % openings.len()];
+        let first_is_white = game % 2 == 0;
+        let [first_player, second_player] = &mut players;
+        let (white, black) = if first_is_white {
+            (first_player, second_player)
+        } else {
+            (second_player, first_player)
+        };
+        let played = play_game(white, black, fen, move_time)?;
+
+        match played.result.winner {
+            None => score.draws += 1,
+            Some(PieceColor::White) if first_is_white => score.wins += 1,
+            Some(PieceColor::Black) if !first_is_white => score.wins += 1,
+            Some(_) => score.losses += 1,
+        }
+        println!(
+            "Game {} of {}: {} vs {}: {}",
+            game + 1,
+            games,
+            white.name,
+            black.name,
+            played.result.description()
+        );
+
+        if !pgn.is_empty() {
+            pgn.push('\n');
+        }
+        pgn.push_str(&game_to_pgn(
+            &played,
+            game + 1,
+            &white.name,
+            &black.name,
+            fen,
+        ));
+        std::fs::write(&pgn_path, &pgn).map_err(|err| format!("{}: {}", pgn_path, err))?;
+    }
+
+    println!(
+        "{} vs {}: {} wins, {} draws, {} losses ({:.1}%)",
+        players[0].name,
+        players[1].name,
+        score.wins,
+        score.draws,
+        score.losses,
+        score.share() * 100.
+    );
+    match score.elo() {
+        Some((difference, margin)) => {
+            println!("Elo difference: {:+.1} +/- {:.1}", difference, margin)
+        }
+        None => println!("Elo difference: unknown, as one player scored every point"),
+    }
+    println!("Games written to {}", pgn_path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_quarters_of_the_points_is_about_191_elo() {
+        let score = Score {
+            wins: 3,
+            draws: 0,
+            losses: 1,
+        };
+        let (difference, _) = score.elo().unwrap();
+        assert!((difference - 190.85).abs() < 0.01);
+    }
+
+    #[test]
+    fn all_draws_leave_no_margin_of_error() {
+        let score = Score {
+            wins: 0,
+            draws: 10,
+            losses: 0,
+        };
+        let (difference, margin) = score.elo().unwrap();
+        assert_eq!(difference, 0.);
+        assert_eq!(margin, 0.);
+    }
+
+    #[test]
+    fn a_clean_sweep_has_no_elo_difference() {
+        let score = Score {
+            wins: 4,
+            draws: 0,
+            losses: 0,
+        };
+        assert!(score.elo().is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! Chess engines run as separate processes, spoken to over the Universal Chess Interface.
+
+use crate::{
+    ai::{SearchResult, CHECKMATE},
+    bitboard::Position,
+    pieces::{parse_square, Move},
+};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError},
+    },
+    time::{Duration, Instant},
+};
+
+/// How often `stop` is checked while the engine analyses.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long past its move time an engine has to answer before it's out of time.
+const MOVE_TIME_MARGIN: Duration = Duration::from_millis(1000);
+
+pub struct UciEngine {
+    process: Child,
+    input: ChildStdin,
+    /// The engine's output a line at a time, read on another thread so it can be waited on with
+    /// a timeout
+    lines: Receiver<String>,
+}
+
+fn engine_quit() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "the engine quit")
+}
+
+/// Reads a move in UCI notation, e.g. `g1f3`. Promotions, e.g. `e7e8q`, aren't read, as the game
+/// has none.
+fn parse_uci_move(text: &str) -> Option<Move> {
+    if text.len() != 4 {
+        return None;
+    }
+    Some(Move {
+        from: text.get(..2).and_then(parse_square)?,
+        to: text.get(2..4).and_then(parse_square)?,
+    })
+}
+
+/// Whether `text` is a promotion in UCI notation, e.g. `e7e8q`.
+fn is_promotion(text: &str) -> bool {
+    text.len() == 5 && text.get(..4).and_then(parse_uci_move).is_some()
+}
+
+/// Reads the depth, score and PV out of an `info` line, if it has all three.
+fn parse_info(line: &str) -> Option<SearchResult> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("info") {
+        return None;
+    }
+
+    let mut depth = None;
+    let mut score = None;
+    let mut pv = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" => depth = tokens.next().and_then(|depth| depth.parse().ok()),
+            "score" => {
+                score = match (
+                    tokens.next(),
+                    tokens.next().and_then(|n| n.parse::<i32>().ok()),
+                ) {
+                    (Some("cp"), Some(centipawns)) => Some(centipawns),
+                    // Mates are given in moves, and scored here by how many plies away they are
+                    (Some("mate"), Some(moves)) if moves > 0 => Some(CHECKMATE - (2 * moves - 1)),
+                    (Some("mate"), Some(moves)) => Some(-2 * moves - CHECKMATE),
+                    _ => None,
+                }
+            }
+            // The PV is always last, and is cut short at any promotion
+            "pv" => pv = tokens.by_ref().map_while(parse_uci_move).collect(),
+            _ => {}
+        }
+    }
+
+    if pv.is_empty() {
+        return None;
+    }
+    Some(SearchResult {
+        depth: depth?,
+        score: score?,
+        pv,
+    })
+}
+
+impl UciEngine {
+    /// Starts the engine at `path` and waits until it's ready to search.
+    pub fn start(path: &str) -> io::Result<Self> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = process.stdin.take();
+        let output = process.stdout.take();
+        let (input, output) = match (input, output) {
+            (Some(input), Some(output)) => (input, output),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "no pipes to the engine",
+                ))
+            }
+        };
+
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            // Ends once the engine quits and its output closes, or the engine is dropped
+            for line in BufReader::new(output).lines() {
+                match line {
+                    Ok(line) if sender.send(line.trim().to_string()).is_ok() => {}
+                    _ => break,
+                }
+            }
+        });
+
+        let mut engine = Self {
+            process,
+            input,
+            lines,
+        };
+        engine.send("uci")?;
+        engine.wait_for("uciok")?;
+        engine.send("isready")?;
+        engine.wait_for("readyok")?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.input, "{}", command)?;
+        self.input.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        self.lines.recv().map_err(|_| engine_quit())
+    }
+
+    /// Skips everything the engine says until a line starting with `reply`.
+    fn wait_for(&mut self, reply: &str) -> io::Result<String> {
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with(reply) {
+                return Ok(line);
+            }
+        }
+    }
+
+    /// Tells the engine the next position comes from a different game.
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.wait_for("readyok")?;
+        Ok(())
+    }
+
+    /// Searches for `time` and returns the move the engine would play, or None if it has none.
+    /// An engine that hasn't answered by `MOVE_TIME_MARGIN` after that, or that answers with a
+    /// promotion, gets an error.
+    pub fn best_move(&mut self, position: &Position, time: Duration) -> io::Result<Option<Move>> {
+        self.send(&format!("position fen {}", position.fen()))?;
+        self.send(&format!("go movetime {}", time.as_millis()))?;
+
+        let deadline = Instant::now() + time + MOVE_TIME_MARGIN;
+        let line = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) if line.starts_with("bestmove") => break line,
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    // Its answer is skipped over by whatever waits on it next
+                    self.send("stop")?;
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "ran out of time"));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(engine_quit()),
+            }
+        };
+
+        match line.split_whitespace().nth(1) {
+            Some(text) if is_promotion(text) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("played the illegal move {}", text),
+            )),
+            text => Ok(text.and_then(parse_uci_move)),
+        }
+    }
+
+    /// Searches until `stop` is set, passing on each depth's result as the engine reports it.
+    pub fn analyse(
+        &mut self,
+        position: &Position,
+        stop: &AtomicBool,
+        mut on_result: impl FnMut(SearchResult),
+    ) -> io::Result<()> {
+        self.send(&format!("position fen {}", position.fen()))?;
+        self.send("go infinite")?;
+
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                self.send("stop")?;
+                self.wait_for("bestmove")?;
+                return Ok(());
+            }
+
+            let line = match self.lines.recv_timeout(STOP_POLL_INTERVAL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Err(engine_quit()),
+            };
+            if line.starts_with("bestmove") {
+                return Ok(());
+            }
+            if let Some(result) = parse_info(&line) {
+                on_result(result);
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        if self.send("quit").is_err() || self.process.wait().is_err() {
+            let _ = self.process.kill();
+        }
+    }
+}
@@ -1,4 +1,25 @@
-use crate::{board::PlayerTurn, pieces::PieceColor};
+use crate::{
+    ai,
+    analysis::Analysis,
+    annotations::Annotations,
+    board::{
+        moves_on_board, Clock, GameMode, GameResult, GameSettings, MoveHistory, PlayerTurn,
+        Termination,
+    },
+    book::OpeningBook,
+    camera::BoardView,
+    hint::{HintRequest, Hints},
+    keyboard::MoveEntry,
+    openings::Openings,
+    pgn,
+    piece_set::{PieceSet, PieceSets},
+    pieces::{apply_move, Move, PieceColor},
+    replay::ReplayCursor,
+    review::{self, GameReview},
+    tablebase::{Probe, Tablebases},
+    theme::{Theme, Themes},
+    AppState, APP_STATE_STAGE,
+};
 use bevy::prelude::*;
 
 struct NextMoveText;
@@ -59,11 +80,1038 @@ fn update_next_move_text(
     }
 }
 
+struct UiMaterials {
+    none: Handle<ColorMaterial>,
+    panel: Handle<ColorMaterial>,
+    button: Handle<ColorMaterial>,
+    button_hovered: Handle<ColorMaterial>,
+    button_pressed: Handle<ColorMaterial>,
+    eval_white: Handle<ColorMaterial>,
+    eval_black: Handle<ColorMaterial>,
+}
+
+impl FromResources for UiMaterials {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut materials = resources.get_mut::<Assets<ColorMaterial>>().unwrap();
+        UiMaterials {
+            none: materials.add(Color::NONE.into()),
+            panel: materials.add(Color::rgba(0.05, 0.05, 0.05, 0.9).into()),
+            button: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+            button_hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+            button_pressed: materials.add(Color::rgb(0.35, 0.75, 0.35).into()),
+            eval_white: materials.add(Color::rgb(0.9, 0.9, 0.9).into()),
+            eval_black: materials.add(Color::rgb(0.1, 0.1, 0.1).into()),
+        }
+    }
+}
+
+/// Marks the top-level node of a menu screen, so it can be despawned when the screen is left.
+struct ScreenRoot;
+
+#[derive(Clone, Copy)]
+enum MenuButton {
+    HumanVsHuman,
+    HumanVsAi,
+    LoadGame,
+    Settings,
+    TogglePlayAs,
+    CycleClock,
+    ToggleSquareNames,
+    CycleTheme,
+    CyclePieceSet,
+    CycleAnimationSpeed,
+    ToggleBook,
+    ToggleAnalysis,
+    CycleDifficulty,
+    CycleStyle,
+    Back,
+    Resign,
+    Rematch,
+    ExportPgn,
+    MainMenu,
+    FlipBoard,
+    ToggleRenderMode,
+    Hint,
+}
+
+/// Marks the text of a settings button, so it can show the current value.
+struct SettingLabel(MenuButton);
+
+struct ClockText;
+
+/// The book moves in the current position, while the game is in the opening book.
+struct BookText;
+
+/// The move suggested by a hint, and how many hints the side to move has used.
+struct HintText;
+
+/// The name of the opening on the board, once it's been recognised.
+struct OpeningText;
+
+/// The result with best play, once the endgame is in the tablebases.
+struct TablebaseText;
+
+/// White's share of the evaluation bar, growing from the bottom.
+struct EvalBarFill;
+
+/// The evaluation and the best line found, e.g. "+0.35 depth 6: e4 e5 Nf3".
+struct AnalysisText;
+
+/// The move being typed, or a hint on how to play with the keyboard.
+struct MoveEntryText;
+
+/// Feedback shown on the game-over screen, e.g. where the PGN was exported to.
+struct StatusText;
+
+struct ReviewText;
+
+struct ReplayText;
+
+fn text_bundle(value: String, font: Handle<Font>, font_size: f32) -> TextBundle {
+    TextBundle {
+        text: Text {
+            value,
+            font,
+            style: TextStyle {
+                font_size,
+                color: Color::rgb(0.9, 0.9, 0.9),
+                ..Default::default()
+            },
+        },
+        style: Style {
+            margin: Rect::all(Val::Px(10.)),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Spawns a centered panel that lays its children out from top to bottom.
+fn spawn_screen(
+    commands: &mut Commands,
+    materials: &UiMaterials,
+    spawn_children: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: materials.none.clone(),
+            ..Default::default()
+        })
+        .with(ScreenRoot)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        padding: Rect::all(Val::Px(20.)),
+                        ..Default::default()
+                    },
+                    material: materials.panel.clone(),
+                    ..Default::default()
+                })
+                .with_children(spawn_children);
+        });
+}
+
+fn button_bundle(materials: &UiMaterials) -> ButtonBundle {
+    ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Px(300.), Val::Px(50.)),
+            margin: Rect::all(Val::Px(5.)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: materials.button.clone(),
+        ..Default::default()
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    materials: &UiMaterials,
+    font: Handle<Font>,
+    label: &str,
+    button: MenuButton,
+) {
+    parent
+        .spawn(button_bundle(materials))
+        .with(button)
+        .with_children(|parent| {
+            parent.spawn(text_bundle(label.to_string(), font, 30.));
+        });
+}
+
+/// Minutes per player that the clock setting cycles through.
+const TIME_CONTROLS: [Option<u32>; 4] = [None, Some(5), Some(10), Some(30)];
+
+/// Seconds per move that the animation setting cycles through.
+const ANIMATION_SPEEDS: [(&str, f32); 4] = [
+    ("Normal", 0.3),
+    ("Slow", 0.6),
+    ("Instant", 0.),
+    ("Fast", 0.15),
+];
+
+fn setting_label(
+    button: MenuButton,
+    settings: &GameSettings,
+    theme: &Theme,
+    piece_set: &PieceSet,
+) -> String {
+    match button {
+        MenuButton::TogglePlayAs => format!(
+            "Play as: {}",
+            match settings.human_color {
+                PieceColor::White => "White",
+                PieceColor::Black => "Black",
+            }
+        ),
+        MenuButton::CycleDifficulty => format!("Difficulty: {}", settings.difficulty.name()),
+        MenuButton::CycleStyle => format!("Style: {}", settings.style.name()),
+        MenuButton::CycleClock => match settings.time_control {
+            Some(minutes) => format!("Clock: {} min", minutes),
+            None => "Clock: Off".to_string(),
+        },
+        MenuButton::ToggleSquareNames => format!(
+            "Square names: {}",
+            if settings.show_square_names {
+                "On"
+            } else {
+                "Off"
+            }
+        ),
+        MenuButton::CycleTheme => format!("Theme: {}", theme.name),
+        MenuButton::CyclePieceSet => format!("Pieces: {}", piece_set.name),
+        MenuButton::CycleAnimationSpeed => format!(
+            "Animation: {}",
+            ANIMATION_SPEEDS
+                .iter()
+                .find(|(_, duration)| *duration == settings.move_duration)
+                .map_or("Custom", |(name, _)| name)
+        ),
+        MenuButton::ToggleAnalysis => format!(
+            "Analysis: {}",
+            if settings.show_analysis { "On" } else { "Off" }
+        ),
+        MenuButton::ToggleBook => format!(
+            "Opening book: {}",
+            if settings.use_book {
+                &settings.book_path
+            } else {
+                "Off"
+            }
+        ),
+        _ => String::new(),
+    }
+}
+
+fn spawn_main_menu(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<UiMaterials>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    spawn_screen(commands, &materials, |parent| {
+        parent.spawn(text_bundle("Chess".to_string(), font.clone(), 60.));
+        for &(label, button) in [
+            ("Human vs Human", MenuButton::HumanVsHuman),
+            ("Human vs AI", MenuButton::HumanVsAi),
+            ("Load Game", MenuButton::LoadGame),
+            ("Settings", MenuButton::Settings),
+        ]
+        .iter()
+        {
+            spawn_button(parent, &materials, font.clone(), label, button);
+        }
+    });
+}
+
+fn spawn_settings(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<UiMaterials>,
+    settings: Res<GameSettings>,
+    theme: Res<Theme>,
+    piece_set: Res<PieceSet>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    spawn_screen(commands, &materials, |parent| {
+        parent.spawn(text_bundle("Settings".to_string(), font.clone(), 60.));
+        for &button in [
+            MenuButton::TogglePlayAs,
+            MenuButton::CycleDifficulty,
+            MenuButton::CycleStyle,
+            MenuButton::CycleClock,
+            MenuButton::ToggleSquareNames,
+            MenuButton::CycleTheme,
+            MenuButton::CyclePieceSet,
+            MenuButton::CycleAnimationSpeed,
+            MenuButton::ToggleBook,
+            MenuButton::ToggleAnalysis,
+        ]
+        .iter()
+        {
+            parent
+                .spawn(button_bundle(&materials))
+                .with(button)
+                .with_children(|parent| {
+                    parent
+                        .spawn(text_bundle(
+                            setting_label(button, &settings, &theme, &piece_set),
+                            font.clone(),
+                            30.,
+                        ))
+                        .with(SettingLabel(button));
+                });
+        }
+        spawn_button(parent, &materials, font.clone(), "Back", MenuButton::Back);
+    });
+}
+
+/// Rewrites the labels once a setting, the theme or the piece set changes. They're written out
+/// when the screen is spawned, so only a click on one of them calls for it.
+fn update_settings_text(
+    _changed: Or<(
+        Option<ChangedRes<GameSettings>>,
+        Option<ChangedRes<Theme>>,
+        Option<ChangedRes<PieceSet>>,
+    )>,
+    settings: Res<GameSettings>,
+    theme: Res<Theme>,
+    piece_set: Res<PieceSet>,
+    mut query: Query<(&mut Text, &SettingLabel)>,
+) {
+    for (mut text, label) in query.iter_mut() {
+        text.value = setting_label(label.0, &settings, &theme, &piece_set);
+    }
+}
+
+/// Spawns the evaluation bar on the left of the screen, with the best line under it.
+fn spawn_eval_bar(commands: &mut Commands, materials: &UiMaterials, font: Handle<Font>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    top: Val::Px(80.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: materials.none.clone(),
+            ..Default::default()
+        })
+        .with(ScreenRoot)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(24.), Val::Px(400.)),
+                        margin: Rect::all(Val::Px(10.)),
+                        flex_direction: FlexDirection::Column,
+                        ..Default::default()
+                    },
+                    material: materials.eval_black.clone(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(50.)),
+                                ..Default::default()
+                            },
+                            material: materials.eval_white.clone(),
+                            ..Default::default()
+                        })
+                        .with(EvalBarFill);
+                });
+            parent
+                .spawn(text_bundle(String::new(), font, 20.))
+                .with(AnalysisText);
+        });
+}
+
+/// Spawns the clock and the buttons shown while playing.
+fn spawn_game_hud(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<UiMaterials>,
+    settings: Res<GameSettings>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    if settings.show_analysis {
+        spawn_eval_bar(commands, &materials, font.clone());
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexEnd,
+                ..Default::default()
+            },
+            material: materials.none.clone(),
+            ..Default::default()
+        })
+        .with(ScreenRoot)
+        .with_children(|parent| {
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 20.))
+                .with(MoveEntryText);
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 20.))
+                .with(BookText);
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 20.))
+                .with(HintText);
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 20.))
+                .with(OpeningText);
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 20.))
+                .with(TablebaseText);
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 30.))
+                .with(ClockText);
+            spawn_button(
+                parent,
+                &materials,
+                font.clone(),
+                "Flip Board",
+                MenuButton::FlipBoard,
+            );
+            spawn_button(
+                parent,
+                &materials,
+                font.clone(),
+                "2D / 3D",
+                MenuButton::ToggleRenderMode,
+            );
+            spawn_button(parent, &materials, font.clone(), "Hint", MenuButton::Hint);
+            spawn_button(parent, &materials, font, "Resign", MenuButton::Resign);
+        });
+}
+
+fn format_clock(seconds: f32) -> String {
+    let seconds = seconds.ceil() as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn update_clock_text(
+    settings: Res<GameSettings>,
+    clock: Res<Clock>,
+    mut query: Query<&mut Text, With<ClockText>>,
+) {
+    if settings.time_control.is_none() {
+        // The text starts out empty, and only a time control fills it
+        return;
+    }
+
+    let value = format!(
+        "White {}  Black {}",
+        format_clock(clock.white),
+        format_clock(clock.black)
+    );
+    for mut text in query.iter_mut() {
+        // The clocks only show whole seconds, so most frames leave the text as it is
+        if text.value != value {
+            text.value = value.clone();
+        }
+    }
+}
+
+/// Lists the book moves with how often the book plays each, e.g. "Book: e4 45%, d4 35%".
+fn book_text(book: &OpeningBook, history: &MoveHistory) -> String {
+    let moves = book.moves(&history.bitboards());
+    if moves.is_empty() {
+        return String::new();
+    }
+
+    let total = moves.iter().map(|(_, weight)| *weight as u32).sum::<u32>();
+    let pieces = history.position();
+    let moves = moves
+        .iter()
+        .map(|(mv, weight)| {
+            format!(
+                "{} {}%",
+                pgn::san(*mv, &pieces),
+                *weight as u32 * 100 / total
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("Book: {}", moves.join(", "))
+}
+
+/// Writes out the book moves whenever a move is played.
+fn update_book_text(
+    book: Res<OpeningBook>,
+    history: Res<MoveHistory>,
+    mut shown: Local<Option<(Entity, usize)>>,
+    mut query: Query<(Entity, &mut Text), With<BookText>>,
+) {
+    let count = history.0.len();
+    for (entity, mut text) in query.iter_mut() {
+        if *shown == Some((entity, count)) {
+            continue;
+        }
+        *shown = Some((entity, count));
+        text.value = book_text(&book, &history);
+    }
+}
+
+/// Shows the hinted move, e.g. "Hint: Nf3 (2 used)", until the next move is played.
+fn update_hint_text(
+    turn: Res<PlayerTurn>,
+    history: Res<MoveHistory>,
+    hints: Res<Hints>,
+    mut shown: Local<Option<(Entity, usize, Option<Move>, u32)>>,
+    mut query: Query<(Entity, &mut Text), With<HintText>>,
+) {
+    let count = history.0.len();
+    let suggestion = hints.suggestion(count);
+    for (entity, mut text) in query.iter_mut() {
+        let key = Some((entity, count, suggestion, hints.used(turn.0)));
+        if *shown == key {
+            continue;
+        }
+        *shown = key;
+        text.value = suggestion.map_or_else(String::new, |mv| {
+            format!(
+                "Hint: {} ({} used)",
+                pgn::san(mv, &history.position()),
+                hints.used(turn.0)
+            )
+        });
+    }
+}
+
+/// Names the opening, e.g. "B90 Sicilian Defense: Najdorf Variation", as played or replayed.
+/// It's looked up again whenever a move is played or the replay steps.
+fn update_opening_text(
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    openings: Res<Openings>,
+    mut shown: Local<Option<(Entity, usize, usize)>>,
+    mut query: Query<(Entity, &mut Text), With<OpeningText>>,
+) {
+    let count = moves_on_board(&state, &history, &cursor);
+    for (entity, mut text) in query.iter_mut() {
+        let key = Some((entity, count, history.0.len()));
+        if *shown == key {
+            continue;
+        }
+        *shown = key;
+        text.value = openings
+            .after(&history, count)
+            .map_or_else(String::new, |opening| {
+                format!("{} {}", opening.eco, opening.name)
+            });
+    }
+}
+
+/// Shows what the tablebases say about the position on the board, as played or replayed. The
+/// tables are only probed again once a different position is on the board.
+fn update_tablebase_text(
+    state: Res<State<AppState>>,
+    history: Res<MoveHistory>,
+    cursor: Res<ReplayCursor>,
+    tablebases: Res<Tablebases>,
+    mut probed: Local<Option<(u64, Option<Probe>)>>,
+    mut query: Query<&mut Text, With<TablebaseText>>,
+) {
+    if query.iter_mut().next().is_none() {
+        return;
+    }
+
+    let position = history.bitboards_after(moves_on_board(&state, &history, &cursor));
+    let probe = match *probed {
+        Some((key, probe)) if key == position.key => probe,
+        _ => {
+            let probe = tablebases.probe(&position);
+            *probed = Some((position.key, probe));
+            probe
+        }
+    };
+    let value = probe.map_or_else(String::new, |probe| {
+        format!("Tablebase: {}", probe.description(position.side_to_move))
+    });
+    for mut text in query.iter_mut() {
+        // Only touching the text when it changes spares laying it out again every frame
+        if text.value != value {
+            text.value = value.clone();
+        }
+    }
+}
+
+/// A score from White's point of view in pawns, e.g. `+0.35`, or as the moves to mate, e.g. `#-3`.
+fn format_score(white_score: i32) -> String {
+    if ai::is_mate(white_score) {
+        let moves = (ai::CHECKMATE - white_score.abs() + 1) / 2;
+        format!("#{}", moves * white_score.signum())
+    } else {
+        format!("{:+.2}", white_score as f32 / 100.)
+    }
+}
+
+/// Fills the evaluation bar by White's chances, and writes out the best line found, as each
+/// result comes in.
+fn update_analysis_display(
+    analysis: Res<Analysis>,
+    mut shown: Local<u32>,
+    mut bar_query: Query<&mut Style, With<EvalBarFill>>,
+    mut text_query: Query<&mut Text, With<AnalysisText>>,
+) {
+    // The bar and text are spawned together, and a result that comes in first waits for them
+    if *shown == analysis.revision || text_query.iter_mut().next().is_none() {
+        return;
+    }
+    *shown = analysis.revision;
+
+    let (position, pieces) = match &analysis.position {
+        Some(position) => position,
+        None => return,
+    };
+    let result = match &analysis.result {
+        Some(result) => result,
+        None => return,
+    };
+
+    let white_score = match position.side_to_move {
+        PieceColor::White => result.score,
+        PieceColor::Black => -result.score,
+    };
+    // Material counts for less the further ahead a side already is
+    let white_share = if ai::is_mate(white_score) {
+        if white_score > 0 {
+            1.
+        } else {
+            0.
+        }
+    } else {
+        1. / (1. + 10f32.powf(-white_score as f32 / 400.))
+    };
+    for mut style in bar_query.iter_mut() {
+        style.size.height = Val::Percent(white_share * 100.);
+    }
+
+    let mut pieces = pieces.clone();
+    let mut line = Vec::new();
+    for mv in result.pv.iter().take(8) {
+        line.push(pgn::san(*mv, &pieces));
+        apply_move(&mut pieces, *mv);
+    }
+    for mut text in text_query.iter_mut() {
+        text.value = format!(
+            "{} depth {}: {}",
+            format_score(white_score),
+            result.depth,
+            line.join(" ")
+        );
+    }
+}
+
+/// Shows the move being typed, rewritten only when the entry changes.
+fn update_move_entry_text(
+    entry: Res<MoveEntry>,
+    mut shown: Local<Option<(Entity, bool, String, Option<String>)>>,
+    mut query: Query<(Entity, &mut Text), With<MoveEntryText>>,
+) {
+    for (entity, mut text) in query.iter_mut() {
+        let key = Some((
+            entity,
+            entry.typing,
+            entry.text.clone(),
+            entry.error.clone(),
+        ));
+        if *shown == key {
+            continue;
+        }
+        *shown = key;
+
+        text.value = match (&entry.error, entry.typing) {
+            (Some(error), true) => format!("{}: {}_", error, entry.text),
+            (None, true) => format!("Move: {}_", entry.text),
+            _ => "Tab to type a move, arrows and Enter to pick squares, R to read the board"
+                .to_string(),
+        };
+    }
+}
+
+fn spawn_game_over(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<UiMaterials>,
+    result: Res<GameResult>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    spawn_screen(commands, &materials, |parent| {
+        parent.spawn(text_bundle(result.description(), font.clone(), 50.));
+        for &(label, button) in [
+            ("Rematch (swap colors)", MenuButton::Rematch),
+            ("Export PGN", MenuButton::ExportPgn),
+            ("Main Menu", MenuButton::MainMenu),
+        ]
+        .iter()
+        {
+            spawn_button(parent, &materials, font.clone(), label, button);
+        }
+        parent
+            .spawn(text_bundle(String::new(), font.clone(), 20.))
+            .with(StatusText);
+        parent
+            .spawn(text_bundle(String::new(), font.clone(), 20.))
+            .with(ReviewText);
+    });
+}
+
+/// Lists the game's moves with the review's marks as they come in.
+fn update_review_text(
+    history: Res<MoveHistory>,
+    review: Res<GameReview>,
+    mut shown: Local<Option<(Entity, usize)>>,
+    mut query: Query<(Entity, &mut Text), With<ReviewText>>,
+) {
+    for (entity, mut text) in query.iter_mut() {
+        let key = Some((entity, review.moves.len()));
+        if *shown == key {
+            continue;
+        }
+        *shown = key;
+        text.value = review::move_list(&history, &review);
+    }
+}
+
+fn spawn_replay_text(
+    commands: &mut Commands,
+    asset_server: Res<AssetServer>,
+    materials: Res<UiMaterials>,
+    settings: Res<GameSettings>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    if settings.show_analysis {
+        spawn_eval_bar(commands, &materials, font.clone());
+    }
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.),
+                    bottom: Val::Px(10.),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                ..Default::default()
+            },
+            material: materials.none.clone(),
+            ..Default::default()
+        })
+        .with(ScreenRoot)
+        .with_children(|parent| {
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 30.))
+                .with(ReplayText);
+            parent
+                .spawn(text_bundle(String::new(), font.clone(), 20.))
+                .with(OpeningText);
+            parent
+                .spawn(text_bundle(String::new(), font, 20.))
+                .with(TablebaseText);
+        });
+}
+
+fn update_replay_text(
+    cursor: ChangedRes<ReplayCursor>,
+    history: Res<MoveHistory>,
+    mut query: Query<&mut Text, With<ReplayText>>,
+) {
+    for mut text in query.iter_mut() {
+        text.value = format!(
+            "Move {} / {} (Left/Right to step, F to flip, V for 2D, R to read the board, Esc for menu)",
+            cursor.0,
+            history.0.len()
+        );
+    }
+}
+
+fn despawn_screen(commands: &mut Commands, query: Query<Entity, With<ScreenRoot>>) {
+    for entity in query.iter() {
+        commands.despawn_recursive(entity);
+    }
+}
+
+fn button_colors(
+    materials: Res<UiMaterials>,
+    mut query: Query<
+        (&Interaction, &mut Handle<ColorMaterial>),
+        (Mutated<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut material) in query.iter_mut() {
+        *material = match *interaction {
+            Interaction::Clicked => materials.button_pressed.clone(),
+            Interaction::Hovered => materials.button_hovered.clone(),
+            Interaction::None => materials.button.clone(),
+        };
+    }
+}
+
+fn export_pgn(
+    history: &MoveHistory,
+    result: &GameResult,
+    settings: &GameSettings,
+    annotations: &Annotations,
+    openings: &Openings,
+    review: &GameReview,
+    hints: &Hints,
+) -> String {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let path = format!("saves/game-{}.pgn", timestamp);
+
+    match pgn::save(
+        &path,
+        history,
+        result,
+        settings,
+        annotations,
+        openings,
+        review,
+        hints,
+    ) {
+        Ok(()) => format!("Saved to {}", path),
+        Err(err) => format!("Could not save to {}: {}", path, err),
+    }
+}
+
+fn menu_buttons(
+    mut state: ResMut<State<AppState>>,
+    mut settings: ResMut<GameSettings>,
+    mut result: ResMut<GameResult>,
+    turn: Res<PlayerTurn>,
+    history: Res<MoveHistory>,
+    annotations: Res<Annotations>,
+    openings: Res<Openings>,
+    review: Res<GameReview>,
+    mut view: ResMut<BoardView>,
+    hints: Res<Hints>,
+    mut hint_requests: ResMut<Events<HintRequest>>,
+    query: Query<(&Interaction, &MenuButton), Mutated<Interaction>>,
+    mut status_query: Query<&mut Text, With<StatusText>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            MenuButton::HumanVsHuman => {
+                settings.mode = GameMode::HumanVsHuman;
+                state.set_next(AppState::Playing).unwrap();
+            }
+            MenuButton::HumanVsAi => {
+                settings.mode = GameMode::HumanVsAi;
+                state.set_next(AppState::Playing).unwrap();
+            }
+            MenuButton::LoadGame => state.set_next(AppState::Replay).unwrap(),
+            MenuButton::Settings => state.set_next(AppState::Settings).unwrap(),
+            MenuButton::Resign => {
+                // Against the AI only the human resigns, in hot-seat whoever is to move
+                let resigning = match settings.mode {
+                    GameMode::HumanVsAi => settings.human_color,
+                    GameMode::HumanVsHuman => turn.0,
+                };
+                result.winner = Some(resigning.opposite());
+                result.termination = Some(Termination::Resignation);
+                state.set_next(AppState::GameOver).unwrap();
+            }
+            MenuButton::Rematch => {
+                settings.human_color = settings.human_color.opposite();
+                state.set_next(AppState::Playing).unwrap();
+            }
+            MenuButton::ExportPgn => {
+                let message = export_pgn(
+                    &history,
+                    &result,
+                    &settings,
+                    &annotations,
+                    &openings,
+                    &review,
+                    &hints,
+                );
+                for mut text in status_query.iter_mut() {
+                    text.value = message.clone();
+                }
+            }
+            MenuButton::FlipBoard => view.flipped = !view.flipped,
+            MenuButton::Hint => hint_requests.send(HintRequest),
+            MenuButton::ToggleRenderMode => view.toggle_render_mode(),
+            MenuButton::Back | MenuButton::MainMenu => state.set_next(AppState::MainMenu).unwrap(),
+            // Handled by `settings_buttons`
+            _ => {}
+        }
+    }
+}
+
+fn settings_buttons(
+    mut settings: ResMut<GameSettings>,
+    mut theme: ResMut<Theme>,
+    themes: Res<Themes>,
+    mut piece_set: ResMut<PieceSet>,
+    piece_sets: Res<PieceSets>,
+    query: Query<(&Interaction, &MenuButton), Mutated<Interaction>>,
+) {
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            MenuButton::TogglePlayAs => settings.human_color = settings.human_color.opposite(),
+            MenuButton::CycleClock => {
+                let index = TIME_CONTROLS
+                    .iter()
+                    .position(|time_control| *time_control == settings.time_control)
+                    .unwrap_or(0);
+                settings.time_control = TIME_CONTROLS[(index + 1) % TIME_CONTROLS.len()];
+            }
+            MenuButton::ToggleSquareNames => {
+                settings.show_square_names = !settings.show_square_names
+            }
+            MenuButton::CycleTheme => *theme = themes.next_after(&theme.name),
+            MenuButton::CyclePieceSet => *piece_set = piece_sets.next_after(&piece_set.name),
+            MenuButton::CycleAnimationSpeed => {
+                let index = ANIMATION_SPEEDS
+                    .iter()
+                    .position(|(_, duration)| *duration == settings.move_duration)
+                    .unwrap_or(0);
+                settings.move_duration = ANIMATION_SPEEDS[(index + 1) % ANIMATION_SPEEDS.len()].1;
+            }
+            MenuButton::ToggleBook => settings.use_book = !settings.use_book,
+            MenuButton::ToggleAnalysis => settings.show_analysis = !settings.show_analysis,
+            MenuButton::CycleDifficulty => settings.difficulty = settings.difficulty.next(),
+            MenuButton::CycleStyle => settings.style = settings.style.next(),
+            _ => {}
+        }
+    }
+}
+
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(init_next_move_text.system())
-            .add_system(update_next_move_text.system());
+        app.init_resource::<UiMaterials>()
+            .add_startup_system(init_next_move_text.system())
+            .add_system(update_next_move_text.system())
+            .add_system(button_colors.system())
+            .add_system(menu_buttons.system())
+            .add_system(settings_buttons.system())
+            .add_system(update_analysis_display.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::MainMenu,
+                spawn_main_menu.system(),
+            )
+            .on_state_exit(APP_STATE_STAGE, AppState::MainMenu, despawn_screen.system())
+            .on_state_enter(APP_STATE_STAGE, AppState::Settings, spawn_settings.system())
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Settings,
+                update_settings_text.system(),
+            )
+            .on_state_exit(APP_STATE_STAGE, AppState::Settings, despawn_screen.system())
+            .on_state_enter(APP_STATE_STAGE, AppState::Playing, spawn_game_hud.system())
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                update_clock_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                update_book_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                update_hint_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                update_move_entry_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                update_opening_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Playing,
+                update_tablebase_text.system(),
+            )
+            .on_state_exit(APP_STATE_STAGE, AppState::Playing, despawn_screen.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::GameOver,
+                spawn_game_over.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::GameOver,
+                update_review_text.system(),
+            )
+            .on_state_exit(APP_STATE_STAGE, AppState::GameOver, despawn_screen.system())
+            .on_state_enter(
+                APP_STATE_STAGE,
+                AppState::Replay,
+                spawn_replay_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Replay,
+                update_replay_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Replay,
+                update_opening_text.system(),
+            )
+            .on_state_update(
+                APP_STATE_STAGE,
+                AppState::Replay,
+                update_tablebase_text.system(),
+            )
+            .on_state_exit(APP_STATE_STAGE, AppState::Replay, despawn_screen.system());
     }
 }